@@ -0,0 +1,83 @@
+//! 顺序与并行 csv 转换的对比：`cargo bench --bench csv_parallel`
+//!
+//! 输入大小由环境变量 `RCLI_BENCH_MB` 控制，默认 128 MB。
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use clap::Parser;
+use rcli::{
+    cli::csv::CsvOpts,
+    process::{
+        csv_convert::convert_csv,
+        csv_parallel::{convert_csv_parallel, CHUNK_SIZE},
+    },
+};
+
+fn generate(mb: u64) -> Result<tempfile::NamedTempFile> {
+    let file = tempfile::Builder::new().suffix(".csv").tempfile()?;
+    let mut writer = BufWriter::new(file.reopen()?);
+    writeln!(writer, "id,name,club,bio,score,active")?;
+    let mut written = 0;
+    let mut i = 0u64;
+    while written < mb * 1024 * 1024 {
+        let line = format!(
+            "{i},player {i},Juventus,\"joined in {}, \"\"kit\"\" {}\nloan spell\",{}.5,{}\n",
+            1990 + i % 30,
+            i % 99,
+            i % 100,
+            i.is_multiple_of(2)
+        );
+        writer.write_all(line.as_bytes())?;
+        written += line.len() as u64;
+        i += 1;
+    }
+    writer.flush()?;
+    Ok(file)
+}
+
+fn measure(f: impl Fn() -> Result<usize>) -> Result<(Duration, usize)> {
+    // 取三次中最快的一次
+    let mut best = Duration::MAX;
+    let mut rows = 0;
+    for _ in 0..3 {
+        let start = Instant::now();
+        rows = f()?;
+        best = best.min(start.elapsed());
+    }
+    Ok((best, rows))
+}
+
+fn main() -> Result<()> {
+    let mb = std::env::var("RCLI_BENCH_MB")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(128);
+    let input = generate(mb)?;
+    let path = input.path().to_string_lossy().to_string();
+    let jobs = thread::available_parallelism().map_or(1, |n| n.get());
+
+    for format in ["ndjson", "json", "csv"] {
+        let opts = CsvOpts::try_parse_from(["csv", "-i", &path, "-f", format, "--infer-types"])?;
+        let (sequential, rows) =
+            measure(|| convert_csv(&opts, File::open(&path)?, Box::new(io::sink())))?;
+        let (parallel, parallel_rows) =
+            measure(|| convert_csv_parallel(&opts, jobs, CHUNK_SIZE, Box::new(io::sink())))?;
+        assert_eq!(rows, parallel_rows);
+
+        let throughput = |d: Duration| mb as f64 / d.as_secs_f64();
+        println!(
+            "{format:>6}: {rows} rows, sequential {sequential:.2?} ({:.0} MB/s), \
+             {jobs} threads {parallel:.2?} ({:.0} MB/s), {:.1}x",
+            throughput(sequential),
+            throughput(parallel),
+            sequential.as_secs_f64() / parallel.as_secs_f64()
+        );
+    }
+    Ok(())
+}
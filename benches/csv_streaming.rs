@@ -0,0 +1,46 @@
+//! 流式 csv 转换的吞吐量与峰值内存：`cargo bench --bench csv_streaming`
+//!
+//! 输入行数逐级增加十倍，峰值堆内存应保持不变；最大行数由环境变量 `RCLI_BENCH_ROWS` 控制，默认 1000000。
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use std::{io, sync::atomic::Ordering, time::Instant};
+
+use anyhow::Result;
+use clap::Parser;
+use common::{GeneratedCsv, CURRENT, PEAK};
+use rcli::{cli::csv::CsvOpts, process::csv_convert::convert_csv};
+
+fn main() -> Result<()> {
+    let max_rows: usize = std::env::var("RCLI_BENCH_ROWS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1_000_000);
+
+    for format in ["json", "yaml", "ndjson", "csv"] {
+        let opts = CsvOpts::try_parse_from(["csv", "-i", "-", "-f", format, "--infer-types"])?;
+        let mut rows = 10_000;
+        while rows <= max_rows {
+            let mut input = GeneratedCsv::new(rows);
+            let base = CURRENT.load(Ordering::SeqCst);
+            PEAK.store(base, Ordering::SeqCst);
+
+            let start = Instant::now();
+            let n = convert_csv(&opts, &mut input, Box::new(io::sink()))?;
+            let elapsed = start.elapsed();
+            assert_eq!(n, rows);
+
+            let mb = input.bytes as f64 / (1024.0 * 1024.0);
+            let peak = PEAK.load(Ordering::SeqCst) - base;
+            println!(
+                "{format:>6}: {rows:>9} rows, {mb:>7.1} MB input, {elapsed:>9.2?} ({:.0} MB/s), \
+                 peak heap {:.1} KB",
+                mb / elapsed.as_secs_f64(),
+                peak as f64 / 1024.0
+            );
+            rows *= 10;
+        }
+    }
+    Ok(())
+}
@@ -1,35 +1,426 @@
 use std::fmt::Display;
 
 use anyhow::Result;
-use clap::{Parser, ValueEnum};
+use clap::{
+    ArgAction, ArgGroup, ArgMatches, Args, Command, FromArgMatches, Parser, Subcommand, ValueEnum,
+};
+use enum_dispatch::enum_dispatch;
 
-use crate::{process::csv_convert::process_csv, Processor};
+use crate::{
+    process::{
+        csv_cat::process_csv_cat, csv_convert::process_csv, csv_dedupe::process_csv_dedupe,
+        csv_diff::process_csv_diff, csv_from::process_csv_from, csv_groupby::process_csv_groupby,
+        csv_join::process_csv_join, csv_query::process_csv_query, csv_show::process_csv_show,
+        csv_sort::process_csv_sort, csv_split::process_csv_split, csv_stats::process_csv_stats,
+        csv_validate::process_csv_validate,
+    },
+    Processor,
+};
 
 use super::verify_file;
 
+/// `rcli csv -i ...` 直接转换，或使用子命令
+///
+/// clap derive 不会为含嵌套 flatten 的 `Option<Args>` 填充参数组，
+/// 因此这里手动实现参数解析
+#[derive(Debug)]
+pub enum CsvCommand {
+    Sub(CsvSubCommand),
+    Convert(CsvOpts),
+}
+
+#[enum_dispatch(Processor)]
 #[derive(Parser, Debug)]
-pub struct CsvOpts {
-    /// 输入文件
+pub enum CsvSubCommand {
+    #[command(about = "Convert JSON/YAML/TOML records back to CSV")]
+    From(CsvFromOpts),
+    #[command(about = "Summarize each column of a CSV")]
+    Stats(CsvStatsOpts),
+    #[command(about = "Show CSV as a table in the terminal")]
+    Show(CsvShowOpts),
+    #[command(about = "Join two CSV files on a key column")]
+    Join(CsvJoinOpts),
+    #[command(about = "Sort CSV rows by one or more columns")]
+    Sort(CsvSortOpts),
+    #[command(about = "Remove duplicate CSV rows")]
+    Dedupe(CsvDedupeOpts),
+    #[command(about = "Group CSV rows and aggregate columns")]
+    Groupby(CsvGroupByOpts),
+    #[command(about = "Validate CSV rows against a schema file")]
+    Validate(CsvValidateOpts),
+    #[command(about = "Run a SQL query over CSV files")]
+    Query(CsvQueryOpts),
+    #[command(about = "Split a CSV into chunks by row count or column value")]
+    Split(CsvSplitOpts),
+    #[command(about = "Concatenate CSV files, aligning columns by name")]
+    Cat(CsvCatOpts),
+    #[command(about = "Compare two CSV files by primary key, exiting non-zero if they differ")]
+    Diff(CsvDiffOpts),
+}
+
+/// 读取 csv 的公共参数
+#[derive(Parser, Debug)]
+pub struct CsvReadOpts {
+    /// 输入文件，`-` 表示 stdin；支持 xlsx/xls/ods 电子表格
     #[arg(short, long, value_parser = verify_file)]
     pub input: String,
-    /// 输出文件
+    /// 电子表格的工作表名，默认读取第一个工作表
+    #[arg(long)]
+    pub sheet: Option<String>,
+    /// 自定义列名，逗号分隔；无头部时默认为 col1..colN
+    #[arg(long, value_delimiter = ',')]
+    pub columns: Vec<String>,
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+/// csv 方言：分隔符、引号、编码等解析参数
+#[derive(Parser, Debug)]
+pub struct CsvDialectOpts {
+    /// 输入文件是否包含csv头部，默认为true
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
+    pub header: bool,
+    /// 输入文件列分隔符
+    #[arg(short, long, default_value_t = ',')]
+    pub delimiter: char,
+    /// 引号字符
+    #[arg(long, default_value_t = '"')]
+    pub quote: char,
+    /// 转义字符，如 `\\`；默认使用双写引号转义
+    #[arg(long)]
+    pub escape: Option<char>,
+    /// 是否将连续两个引号视为转义的引号
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
+    pub double_quote: bool,
+    /// 注释字符，以该字符开头的行会被忽略
+    #[arg(long)]
+    pub comment: Option<char>,
+    /// 允许各行列数不一致
+    #[arg(long)]
+    pub flexible: bool,
+    /// 去除字段首尾空白
+    #[arg(long)]
+    pub trim: bool,
+    /// 输入编码，如 utf-8、latin1、gbk；`auto` 表示自动检测，默认按 BOM 判断
+    #[arg(long)]
+    pub encoding: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvOpts {
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+    /// 输出文件，`-` 表示 stdout
     #[arg(short, long)]
     pub output: Option<String>,
     /// 输出文件格式
     #[arg(short, long, default_value_t = OutputFormat::Json)]
     pub format: OutputFormat,
-    /// 输入文件是否包含csv头部，默认为true
-    #[arg(long, default_value_t = true)]
-    pub header: bool,
-    /// 输入文件列分隔符
+    /// 推断单元格类型（整数、浮点数、布尔值，空值为 null）
+    #[arg(long)]
+    pub infer_types: bool,
+    /// 列类型定义文件（json/yaml/toml），列名到类型的映射
+    #[arg(long, value_parser = verify_file)]
+    pub schema: Option<String>,
+    /// 输出的列，逗号分隔，按给定顺序输出
+    #[arg(long, value_delimiter = ',')]
+    pub select: Vec<String>,
+    /// 重命名列，形如 `old=new`，可重复或逗号分隔
+    #[arg(long, value_delimiter = ',')]
+    pub rename: Vec<String>,
+    /// 行过滤表达式，如 `age > 30 && nationality == "Italy"`
+    #[arg(long = "where")]
+    pub filter: Option<String>,
+    /// 按列名中的 `.` 与 `[i]` 生成嵌套对象与数组，如 `address.city`、`tags[0]`
+    #[arg(long)]
+    pub unflatten: bool,
+    /// 列出电子表格中的工作表后退出
+    #[arg(long)]
+    pub list_sheets: bool,
+    /// parquet 行组 / arrow record batch 的行数；列类型只根据第一个行组推断，之后类型不符时报错，可用 --schema 指定
+    #[arg(long, default_value_t = 65536)]
+    pub row_group_size: usize,
+    /// parquet 压缩算法
+    #[arg(long, default_value_t = Compression::Snappy)]
+    pub compression: Compression,
+    /// 并行转换的线程数，0 表示使用全部 CPU 核心；仅对较大的 csv 文件生效
+    #[arg(short, long, default_value_t = 0)]
+    pub jobs: usize,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvFromOpts {
+    /// 输入文件（对象数组），`-` 表示 stdin，嵌套对象展开为 `a.b`，数组展开为 `a[0]`
+    #[arg(short, long, value_parser = verify_file)]
+    pub input: String,
+    /// 输出文件，`-` 表示 stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// 输入文件格式，默认根据扩展名判断
+    #[arg(short, long)]
+    pub format: Option<OutputFormat>,
+    /// 输出文件列分隔符
     #[arg(short, long, default_value_t = ',')]
     pub delimiter: char,
 }
 
+#[derive(Parser, Debug)]
+pub struct CsvStatsOpts {
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+    /// 输出格式
+    #[arg(short, long, default_value_t = StatsFormat::Table)]
+    pub format: StatsFormat,
+    /// 每列展示出现次数最多的前 N 个值
+    #[arg(long, default_value_t = 5)]
+    pub top: usize,
+    /// 每列精确统计的不同值个数上限，超过后改用 HyperLogLog 估算
+    #[arg(long, default_value_t = 100_000)]
+    pub exact_limit: usize,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvShowOpts {
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+    /// 只显示前 N 行
+    #[arg(long, conflicts_with = "tail")]
+    pub head: Option<usize>,
+    /// 只显示最后 N 行
+    #[arg(long)]
+    pub tail: Option<usize>,
+    /// 单元格最大显示宽度，超出部分截断
+    #[arg(long, default_value_t = 40)]
+    pub max_width: usize,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvJoinOpts {
+    /// 左侧输入文件，`-` 表示 stdin
+    #[arg(value_parser = verify_file)]
+    pub left: String,
+    /// 右侧输入文件，`-` 表示 stdin
+    #[arg(value_parser = verify_file)]
+    pub right: String,
+    /// 关联列，两侧列名不同时写作 `left_col=right_col`
+    #[arg(long)]
+    pub on: String,
+    /// 关联方式
+    #[arg(long, default_value_t = JoinKind::Inner)]
+    pub how: JoinKind,
+    /// 输出文件，`-` 表示 stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// 输出文件格式
+    #[arg(short, long, default_value_t = OutputFormat::Csv)]
+    pub format: OutputFormat,
+    /// 推断单元格类型（整数、浮点数、布尔值，空值为 null）
+    #[arg(long)]
+    pub infer_types: bool,
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvSortOpts {
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+    /// 排序列，逗号分隔，依次比较
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub by: Vec<String>,
+    /// 降序排列
+    #[arg(long)]
+    pub desc: bool,
+    /// 按数值比较，无法解析为数字的值排在最后
+    #[arg(long)]
+    pub numeric: bool,
+    /// 内存排序上限（MB），超出后分块排序写入临时文件再归并
+    #[arg(long, default_value_t = 256)]
+    pub memory_limit: usize,
+    /// 输出文件，`-` 表示 stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// 输出文件格式
+    #[arg(short, long, default_value_t = OutputFormat::Csv)]
+    pub format: OutputFormat,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvDedupeOpts {
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+    /// 判断重复的列，逗号分隔，默认比较整行；保留首次出现的行
+    #[arg(long, value_delimiter = ',')]
+    pub by: Vec<String>,
+    /// 输出文件，`-` 表示 stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// 输出文件格式
+    #[arg(short, long, default_value_t = OutputFormat::Csv)]
+    pub format: OutputFormat,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvGroupByOpts {
+    /// 分组列，逗号分隔
+    #[arg(value_delimiter = ',', required = true)]
+    pub by: Vec<String>,
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+    /// 聚合函数，逗号分隔：`count`、`count:col`、`sum:col`、`avg:col`、`min:col`、`max:col`
+    #[arg(long, value_delimiter = ',', default_value = "count")]
+    pub agg: Vec<String>,
+    /// 输出文件，`-` 表示 stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// 输出文件格式
+    #[arg(short, long, default_value_t = OutputFormat::Csv)]
+    pub format: OutputFormat,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvValidateOpts {
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+    /// 校验规则文件（json/yaml/toml），JSON Schema 或列名到规则的映射
+    #[arg(long, value_parser = verify_file)]
+    pub schema: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvQueryOpts {
+    /// SQL 查询，如 `SELECT club, count(*) FROM juventus GROUP BY club`
+    pub sql: String,
+    /// 输入文件，以文件名（不含扩展名）作为表名；未指定时读取当前目录下的 `<表名>.csv`
+    #[arg(value_parser = verify_file)]
+    pub files: Vec<String>,
+    /// 输出文件，`-` 表示 stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// 输出文件格式；未指定输出文件与格式时在终端显示为表格
+    #[arg(short, long)]
+    pub format: Option<OutputFormat>,
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+#[derive(Parser, Debug)]
+#[command(group(ArgGroup::new("mode").required(true).args(["rows", "by"])))]
+pub struct CsvSplitOpts {
+    #[command(flatten)]
+    pub read: CsvReadOpts,
+    /// 每个分块的最大行数
+    #[arg(long)]
+    pub rows: Option<usize>,
+    /// 按该列的取值拆分，每个取值写入一个文件
+    #[arg(long)]
+    pub by: Option<String>,
+    /// 输出目录，默认为输入文件所在目录
+    #[arg(short, long)]
+    pub output_dir: Option<String>,
+    /// 输出文件名前缀，默认为输入文件名（不含扩展名）
+    #[arg(long)]
+    pub prefix: Option<String>,
+    /// 按列拆分时同时打开的最多文件数，超出时关闭最久未写入的文件，之后以追加方式重新打开
+    #[arg(long, default_value_t = 64, requires = "by")]
+    pub max_open: usize,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvCatOpts {
+    /// 输入文件，按顺序拼接，`-` 表示 stdin
+    #[arg(required = true, value_parser = verify_file)]
+    pub files: Vec<String>,
+    /// 只保留所有文件共有的列，默认取各文件列的并集
+    #[arg(long)]
+    pub common: bool,
+    /// 输出文件，`-` 表示 stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// 输出文件格式
+    #[arg(short, long, default_value_t = OutputFormat::Csv)]
+    pub format: OutputFormat,
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvDiffOpts {
+    /// 旧文件，`-` 表示 stdin
+    #[arg(value_parser = verify_file)]
+    pub old: String,
+    /// 新文件，`-` 表示 stdin
+    #[arg(value_parser = verify_file)]
+    pub new: String,
+    /// 主键列，逗号分隔，组合键按给定顺序比较
+    #[arg(long, required = true, value_delimiter = ',')]
+    pub key: Vec<String>,
+    /// 输出文件，`-` 表示 stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// 报告格式
+    #[arg(short, long, default_value_t = DiffFormat::Text)]
+    pub format: DiffFormat,
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum JoinKind {
+    Inner,
+    Left,
+    Right,
+    Outer,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum StatsFormat {
+    Table,
+    Json,
+}
+
 #[derive(Debug, Clone, ValueEnum)]
 pub enum OutputFormat {
     Json,
     Yaml,
+    Toml,
+    Ndjson,
+    Tsv,
+    Csv,
+    /// 需要启用 `columnar` feature
+    Parquet,
+    /// Arrow IPC 文件，需要启用 `columnar` feature
+    Arrow,
+}
+
+impl OutputFormat {
+    /// 列式格式需要确定的列类型
+    pub fn is_columnar(&self) -> bool {
+        matches!(self, OutputFormat::Parquet | OutputFormat::Arrow)
+    }
+
+    /// 能表示嵌套对象与数组的格式
+    pub fn is_nested(&self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Json | OutputFormat::Yaml | OutputFormat::Toml | OutputFormat::Ndjson
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DiffFormat {
+    /// 可读的文本报告
+    Text,
+    /// 含新增、删除与逐列修改的 JSON
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    None,
+    Snappy,
+    Zstd,
 }
 
 impl Display for OutputFormat {
@@ -37,6 +428,91 @@ impl Display for OutputFormat {
         match self {
             OutputFormat::Json => write!(f, "json"),
             OutputFormat::Yaml => write!(f, "yaml"),
+            OutputFormat::Toml => write!(f, "toml"),
+            OutputFormat::Ndjson => write!(f, "ndjson"),
+            OutputFormat::Tsv => write!(f, "tsv"),
+            OutputFormat::Csv => write!(f, "csv"),
+            OutputFormat::Parquet => write!(f, "parquet"),
+            OutputFormat::Arrow => write!(f, "arrow"),
+        }
+    }
+}
+
+impl Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Compression::None => write!(f, "none"),
+            Compression::Snappy => write!(f, "snappy"),
+            Compression::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+impl Display for DiffFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiffFormat::Text => write!(f, "text"),
+            DiffFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl Display for JoinKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JoinKind::Inner => write!(f, "inner"),
+            JoinKind::Left => write!(f, "left"),
+            JoinKind::Right => write!(f, "right"),
+            JoinKind::Outer => write!(f, "outer"),
+        }
+    }
+}
+
+impl Display for StatsFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatsFormat::Table => write!(f, "table"),
+            StatsFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl FromArgMatches for CsvCommand {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        Self::from_arg_matches_mut(&mut matches.clone())
+    }
+
+    fn from_arg_matches_mut(matches: &mut ArgMatches) -> Result<Self, clap::Error> {
+        if matches.subcommand_name().is_some() {
+            CsvSubCommand::from_arg_matches_mut(matches).map(CsvCommand::Sub)
+        } else {
+            CsvOpts::from_arg_matches_mut(matches).map(CsvCommand::Convert)
+        }
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl Args for CsvCommand {
+    fn augment_args(cmd: Command) -> Command {
+        CsvSubCommand::augment_subcommands(CsvOpts::augment_args(cmd))
+            .args_conflicts_with_subcommands(true)
+            .subcommand_negates_reqs(true)
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        Self::augment_args(cmd)
+    }
+}
+
+impl Processor for CsvCommand {
+    async fn process(self) -> Result<()> {
+        match self {
+            CsvCommand::Sub(cmd) => cmd.process().await,
+            CsvCommand::Convert(opts) => opts.process().await,
         }
     }
 }
@@ -46,3 +522,75 @@ impl Processor for CsvOpts {
         process_csv(self)
     }
 }
+
+impl Processor for CsvFromOpts {
+    async fn process(self) -> Result<()> {
+        process_csv_from(self)
+    }
+}
+
+impl Processor for CsvStatsOpts {
+    async fn process(self) -> Result<()> {
+        process_csv_stats(self)
+    }
+}
+
+impl Processor for CsvShowOpts {
+    async fn process(self) -> Result<()> {
+        process_csv_show(self)
+    }
+}
+
+impl Processor for CsvJoinOpts {
+    async fn process(self) -> Result<()> {
+        process_csv_join(self)
+    }
+}
+
+impl Processor for CsvSortOpts {
+    async fn process(self) -> Result<()> {
+        process_csv_sort(self)
+    }
+}
+
+impl Processor for CsvDedupeOpts {
+    async fn process(self) -> Result<()> {
+        process_csv_dedupe(self)
+    }
+}
+
+impl Processor for CsvGroupByOpts {
+    async fn process(self) -> Result<()> {
+        process_csv_groupby(self)
+    }
+}
+
+impl Processor for CsvValidateOpts {
+    async fn process(self) -> Result<()> {
+        process_csv_validate(self)
+    }
+}
+
+impl Processor for CsvQueryOpts {
+    async fn process(self) -> Result<()> {
+        process_csv_query(self)
+    }
+}
+
+impl Processor for CsvSplitOpts {
+    async fn process(self) -> Result<()> {
+        process_csv_split(self)
+    }
+}
+
+impl Processor for CsvCatOpts {
+    async fn process(self) -> Result<()> {
+        process_csv_cat(self)
+    }
+}
+
+impl Processor for CsvDiffOpts {
+    async fn process(self) -> Result<()> {
+        process_csv_diff(self)
+    }
+}
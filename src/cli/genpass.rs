@@ -1,35 +1,115 @@
+use std::fmt::Display;
+
 use anyhow::Result;
-use clap::Parser;
-use zxcvbn::zxcvbn;
+use clap::{Parser, ValueEnum};
 
-use crate::{process::gen_pass::process_genpass, Processor};
+use super::verify_file;
+use crate::{
+    process::gen_pass::{process_genpass_entries, write_entries},
+    Processor,
+};
 
 #[derive(Parser, Debug)]
 pub struct GenPassOpts {
     /// 密码长度
     #[arg(short, long, default_value_t = 16)]
     pub length: u8,
-    /// 是否包含小写字母
-    #[arg(long, default_value_t = true)]
-    pub lowercase: bool,
-    /// 是否包含大写字母
-    #[arg(long, default_value_t = true)]
-    pub uppercase: bool,
-    /// 是否包含数字
-    #[arg(long, default_value_t = true)]
-    pub number: bool,
-    /// 是否包含特殊字符
-    #[arg(long, default_value_t = true)]
-    pub symbol: bool,
+    /// 不包含小写字母
+    #[arg(long)]
+    pub no_lowercase: bool,
+    /// 不包含大写字母
+    #[arg(long)]
+    pub no_uppercase: bool,
+    /// 不包含数字
+    #[arg(long)]
+    pub no_number: bool,
+    /// 不包含特殊字符
+    #[arg(long)]
+    pub no_symbol: bool,
+    /// 自定义字符集，支持 `a-z0-9` 形式的范围；按大写、小写、数字与其他字符分组，每组至少出现一次
+    #[arg(
+        long,
+        allow_hyphen_values = true,
+        conflicts_with_all = ["no_lowercase", "no_uppercase", "no_number", "no_symbol", "symbols"]
+    )]
+    pub charset: Option<String>,
+    /// 自定义特殊字符，如 `!@#-`
+    #[arg(long, conflicts_with = "no_symbol", allow_hyphen_values = true)]
+    pub symbols: Option<String>,
+    /// 排除的字符，如 `O0Il1`；类别中的字符被全部排除时该类别不再要求出现
+    #[arg(long, allow_hyphen_values = true)]
+    pub exclude: Option<String>,
+    /// 每个字符最多出现一次
+    #[arg(long)]
+    pub no_repeat: bool,
+    /// 密码策略文件（toml / yaml / json）：各类字符最少个数、最多连续相同字符、禁用子串与 zxcvbn 最低评分
+    #[arg(long, value_parser = verify_file)]
+    pub policy: Option<String>,
+    /// 按策略生成时的最大尝试次数
+    #[arg(long, default_value_t = 1000, requires = "policy")]
+    pub attempts: usize,
+    /// 生成的个数
+    #[arg(long, default_value_t = 1)]
+    pub count: usize,
+    /// 输出格式：plain 只输出密码；json、csv 附带熵、zxcvbn 评分与建议
+    #[arg(long, default_value_t = GenPassFormat::Plain)]
+    pub output: GenPassFormat,
+    /// 生成由指定个数的单词组成的口令短语
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "length", "no_lowercase", "no_uppercase", "no_number", "no_symbol",
+            "charset", "symbols", "exclude", "no_repeat",
+        ]
+    )]
+    pub words: Option<usize>,
+    /// 口令短语的单词分隔符
+    #[arg(long, default_value = "-", requires = "words")]
+    pub separator: String,
+    /// 词表：内置的 `eff-large`、`eff-short`，或每行一个单词的文件（可带骰子编号）
+    #[arg(long, default_value = "eff-large", requires = "words")]
+    pub wordlist: String,
+    /// 单词首字母大写
+    #[arg(long, requires = "words")]
+    pub capitalize: bool,
+    /// 在随机一个单词后追加一位数字
+    #[arg(long, requires = "words")]
+    pub digit: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GenPassFormat {
+    Plain,
+    Json,
+    Csv,
+}
+
+impl Default for GenPassOpts {
+    /// 与命令行不带参数时相同
+    fn default() -> Self {
+        Self::parse_from(["genpass"])
+    }
 }
 
 impl Processor for GenPassOpts {
     async fn process(self) -> Result<()> {
-        let pwd = process_genpass(self)?;
-        println!("{}", pwd);
-
-        let estimate = zxcvbn(&pwd, &[]);
-        eprintln!("Password strength {}", estimate.score());
+        let entries = process_genpass_entries(&self)?;
+        write_entries(&entries, self.output, &mut std::io::stdout().lock())?;
+        if self.output == GenPassFormat::Plain {
+            for entry in &entries {
+                eprintln!("Password strength {}", entry.score);
+            }
+        }
         Ok(())
     }
 }
+
+impl Display for GenPassFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GenPassFormat::Plain => write!(f, "plain"),
+            GenPassFormat::Json => write!(f, "json"),
+            GenPassFormat::Csv => write!(f, "csv"),
+        }
+    }
+}
@@ -9,7 +9,7 @@ use std::path::{Path, PathBuf};
 use anyhow::Result;
 use base64::Base64SubCommand;
 use clap::Parser;
-use csv::CsvOpts;
+use csv::CsvCommand;
 use enum_dispatch::enum_dispatch;
 use genpass::GenPassOpts;
 use http::HttpSubCommand;
@@ -22,11 +22,13 @@ pub struct Opts {
     pub cmd: SubCommand,
 }
 
+// 命令行参数只解析一次，无需为变体大小差异装箱
+#[allow(clippy::large_enum_variant)]
 #[enum_dispatch(Processor)]
 #[derive(Parser, Debug)]
 pub enum SubCommand {
     #[command(about = "Show CSV, or convert CSV to other formats")]
-    Csv(CsvOpts),
+    Csv(CsvCommand),
     #[command(name = "genpass", about = "Generate a random password")]
     GenPass(GenPassOpts),
     #[command(subcommand, about = "Encode or decode base64")]
@@ -59,6 +61,20 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_parse_csv_command() {
+        let opts = Opts::try_parse_from(["rcli", "csv", "-i", "Cargo.toml", "-f", "yaml"]).unwrap();
+        assert!(matches!(
+            opts.cmd,
+            SubCommand::Csv(csv::CsvCommand::Convert(_))
+        ));
+
+        let opts = Opts::try_parse_from(["rcli", "csv", "stats", "-i", "Cargo.toml"]).unwrap();
+        assert!(matches!(opts.cmd, SubCommand::Csv(csv::CsvCommand::Sub(_))));
+
+        assert!(Opts::try_parse_from(["rcli", "csv"]).is_err());
+    }
+
     #[test]
     fn test_verify_input_file() {
         assert_eq!(verify_file("-"), Ok("-".into()));
@@ -0,0 +1,120 @@
+use std::io::{Read, Write};
+
+use anyhow::Result;
+use csv::{Reader, StringRecord};
+
+use crate::{cli::csv::CsvCatOpts, utils::get_writer};
+
+use super::{
+    csv_reader::{dialect_reader, open_input},
+    csv_types::Schema,
+    csv_writer::record_writer,
+};
+
+pub fn process_csv_cat(opts: CsvCatOpts) -> Result<()> {
+    let sources = opts
+        .files
+        .iter()
+        .map(|input| dialect_reader(&opts.dialect, &[], open_input(input)?))
+        .collect::<Result<Vec<_>>>()?;
+    let output = opts.output.as_deref().unwrap_or("-");
+    let rows = cat_csv(&opts, sources, get_writer(output)?)?;
+    eprintln!("{} files -> {output}, {rows} rows, Done.", opts.files.len());
+
+    Ok(())
+}
+
+/// 合并各文件的列：默认取并集，按首次出现的顺序排列；`common` 时只保留所有文件都有的列
+pub fn merge_headers(headers: &[StringRecord], common: bool) -> StringRecord {
+    let mut merged: Vec<&str> = Vec::new();
+    for name in headers.iter().flatten() {
+        if !merged.contains(&name) {
+            merged.push(name);
+        }
+    }
+    if common {
+        merged.retain(|name| headers.iter().all(|h| h.iter().any(|n| n == *name)));
+    }
+    StringRecord::from(merged)
+}
+
+/// 按顺序拼接多个文件，按列名对齐，缺少的列填空值，返回总行数
+pub fn cat_csv<R: Read>(
+    opts: &CsvCatOpts,
+    sources: Vec<(Reader<R>, StringRecord)>,
+    output: Box<dyn Write>,
+) -> Result<usize> {
+    let all: Vec<StringRecord> = sources.iter().map(|(_, h)| h.clone()).collect();
+    let headers = merge_headers(&all, opts.common);
+
+    let schema = Schema::new(Default::default(), false);
+    let mut writer = record_writer(&opts.format, output)?;
+    writer.write_header(&headers)?;
+
+    let mut rows = 0;
+    for (input, (mut reader, file_headers)) in opts.files.iter().zip(sources) {
+        let indices: Vec<Option<usize>> = headers
+            .iter()
+            .map(|name| file_headers.iter().position(|h| h == name))
+            .collect();
+        let missing: Vec<&str> = headers
+            .iter()
+            .zip(&indices)
+            .filter(|(_, idx)| idx.is_none())
+            .map(|(name, _)| name)
+            .collect();
+        if !missing.is_empty() {
+            eprintln!("{input}: missing columns {missing:?}, filled with empty values");
+        }
+
+        let mut record = StringRecord::new();
+        while reader.read_record(&mut record)? {
+            let aligned: StringRecord = indices
+                .iter()
+                .map(|idx| idx.and_then(|i| record.get(i)).unwrap_or_default())
+                .collect();
+            writer.write_record(&schema.record_to_value(&headers, &aligned)?)?;
+            rows += 1;
+        }
+    }
+    writer.finish()?;
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::utils::SharedBuf;
+
+    fn cat(args: &[&str], inputs: &[&'static str]) -> Result<String> {
+        let files = inputs.iter().map(|_| "-");
+        let opts = CsvCatOpts::try_parse_from(
+            ["cat"].into_iter().chain(files).chain(args.iter().copied()),
+        )?;
+        let sources = inputs
+            .iter()
+            .map(|input| dialect_reader(&opts.dialect, &[], input.as_bytes()))
+            .collect::<Result<Vec<_>>>()?;
+        let buf = SharedBuf::default();
+        cat_csv(&opts, sources, Box::new(buf.clone()))?;
+        buf.contents()
+    }
+
+    #[test]
+    fn test_cat_reconciles_headers() -> Result<()> {
+        let inputs = [
+            "name,kit\nBuffon,77\n",
+            "kit,name,club\n10,Dybala,Juventus\n",
+            "name\nKean\n",
+        ];
+        assert_eq!(
+            cat(&[], &inputs)?,
+            "name,kit,club\nBuffon,77,\nDybala,10,Juventus\nKean,,\n"
+        );
+        assert_eq!(cat(&["--common"], &inputs)?, "name\nBuffon\nDybala\nKean\n");
+        Ok(())
+    }
+}
@@ -0,0 +1,319 @@
+use std::{
+    fs::File,
+    io::{self, Seek, Write},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Result};
+use arrow_array::{
+    builder::{BooleanBuilder, Float64Builder, Int64Builder, StringBuilder},
+    ArrayRef, RecordBatch,
+};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use csv::StringRecord;
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression as ParquetCompression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+use serde_json::Value;
+
+use crate::cli::csv::{Compression, OutputFormat};
+
+use super::csv_writer::{RecordWriter, WriterOptions};
+
+/// parquet / arrow 写出器
+///
+/// 记录按 `row_group_size` 行缓存为一批，列类型由第一批数据推断；
+/// 后续批次中类型不符的值会报错，此时可用 `--schema` 指定列类型。
+/// 编码结果先写入临时文件，全部写完才复制到输出，因此报错时输出中不会留下不完整的文件。
+pub struct ColumnarWriter {
+    format: OutputFormat,
+    options: WriterOptions,
+    headers: StringRecord,
+    rows: Vec<Value>,
+    output: Box<dyn Write>,
+    sink: Option<Sink>,
+    schema: SchemaRef,
+    written: usize,
+}
+
+enum Sink {
+    Parquet(ArrowWriter<File>),
+    Arrow(FileWriter<File>),
+}
+
+impl ColumnarWriter {
+    pub fn new(format: OutputFormat, writer: Box<dyn Write>, options: WriterOptions) -> Self {
+        Self {
+            format,
+            options,
+            headers: StringRecord::new(),
+            rows: Vec::new(),
+            output: writer,
+            sink: None,
+            schema: Arc::new(Schema::empty()),
+            written: 0,
+        }
+    }
+
+    fn flush_batch(&mut self) -> Result<()> {
+        if self.sink.is_none() {
+            let fields: Vec<Field> = self
+                .headers
+                .iter()
+                .map(|name| Field::new(name, infer_data_type(&self.rows, name), true))
+                .collect();
+            self.schema = Arc::new(Schema::new(fields));
+            self.sink = Some(self.open(tempfile::tempfile()?)?);
+        }
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+
+        let batch = build_batch(&self.schema, &self.rows, self.written)?;
+        self.written += self.rows.len();
+        self.rows.clear();
+        match self.sink.as_mut() {
+            Some(Sink::Parquet(writer)) => {
+                writer.write(&batch)?;
+                writer.flush()?;
+            }
+            Some(Sink::Arrow(writer)) => writer.write(&batch)?,
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn open(&self, spool: File) -> Result<Sink> {
+        Ok(match self.format {
+            OutputFormat::Arrow => Sink::Arrow(FileWriter::try_new(spool, &self.schema)?),
+            _ => {
+                let compression = match self.options.compression {
+                    Compression::None => ParquetCompression::UNCOMPRESSED,
+                    Compression::Snappy => ParquetCompression::SNAPPY,
+                    Compression::Zstd => ParquetCompression::ZSTD(ZstdLevel::default()),
+                };
+                let props = WriterProperties::builder()
+                    .set_max_row_group_size(self.options.row_group_size.max(1))
+                    .set_compression(compression)
+                    .build();
+                Sink::Parquet(ArrowWriter::try_new(
+                    spool,
+                    self.schema.clone(),
+                    Some(props),
+                )?)
+            }
+        })
+    }
+}
+
+impl RecordWriter for ColumnarWriter {
+    fn write_header(&mut self, headers: &StringRecord) -> Result<()> {
+        self.headers = headers.clone();
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        self.rows.push(record.clone());
+        if self.rows.len() >= self.options.row_group_size {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        // 按行组写出，未满的行组留到 finish
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.flush_batch()?;
+        let mut spool = match self.sink.take() {
+            Some(Sink::Parquet(writer)) => writer.into_inner()?,
+            Some(Sink::Arrow(writer)) => writer.into_inner()?,
+            None => return Ok(()),
+        };
+        spool.rewind()?;
+        io::copy(&mut spool, &mut self.output)?;
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+/// 由已缓存的值推断列类型：整数与浮点数合并为浮点数，其余不一致或全为空时为字符串
+fn infer_data_type(rows: &[Value], name: &str) -> DataType {
+    let mut ty: Option<DataType> = None;
+    for value in rows.iter().filter_map(|row| row.get(name)) {
+        let current = match value {
+            Value::Null => continue,
+            Value::Bool(_) => DataType::Boolean,
+            Value::Number(n) if n.is_f64() => DataType::Float64,
+            Value::Number(_) => DataType::Int64,
+            _ => DataType::Utf8,
+        };
+        ty = Some(match (ty, current) {
+            (None, current) => current,
+            (Some(a), b) if a == b => a,
+            (Some(DataType::Int64), DataType::Float64)
+            | (Some(DataType::Float64), DataType::Int64) => DataType::Float64,
+            _ => DataType::Utf8,
+        });
+        if ty == Some(DataType::Utf8) {
+            break;
+        }
+    }
+    ty.unwrap_or(DataType::Utf8)
+}
+
+/// 按推断的列类型构建一批数据，`offset` 为之前已写出的行数，用于报错
+fn build_batch(schema: &SchemaRef, rows: &[Value], offset: usize) -> Result<RecordBatch> {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let name = field.name();
+            let values = rows.iter().map(|row| row.get(name).unwrap_or(&Value::Null));
+            let mismatch = |row: usize, value: &Value| {
+                anyhow!(
+                    "row {}: column {name:?} was inferred as {} but got {value}, \
+                     use --schema to set its type",
+                    offset + row + 1,
+                    field.data_type()
+                )
+            };
+            let array: ArrayRef = match field.data_type() {
+                DataType::Int64 => {
+                    let mut builder = Int64Builder::with_capacity(rows.len());
+                    for (row, value) in values.enumerate() {
+                        match value {
+                            Value::Null => builder.append_null(),
+                            v => builder.append_value(v.as_i64().ok_or_else(|| mismatch(row, v))?),
+                        }
+                    }
+                    Arc::new(builder.finish())
+                }
+                DataType::Float64 => {
+                    let mut builder = Float64Builder::with_capacity(rows.len());
+                    for (row, value) in values.enumerate() {
+                        match value {
+                            Value::Null => builder.append_null(),
+                            v => builder.append_value(v.as_f64().ok_or_else(|| mismatch(row, v))?),
+                        }
+                    }
+                    Arc::new(builder.finish())
+                }
+                DataType::Boolean => {
+                    let mut builder = BooleanBuilder::with_capacity(rows.len());
+                    for (row, value) in values.enumerate() {
+                        match value {
+                            Value::Null => builder.append_null(),
+                            v => builder.append_value(v.as_bool().ok_or_else(|| mismatch(row, v))?),
+                        }
+                    }
+                    Arc::new(builder.finish())
+                }
+                _ => {
+                    let mut builder = StringBuilder::new();
+                    for value in values {
+                        match value {
+                            Value::Null => builder.append_null(),
+                            Value::String(s) => builder.append_value(s),
+                            v => builder.append_value(v.to_string()),
+                        }
+                    }
+                    Arc::new(builder.finish())
+                }
+            };
+            Ok(array)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use arrow_array::{cast::AsArray, types::Int64Type, Array};
+    use arrow_ipc::reader::FileReader;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use serde_json::json;
+
+    use super::*;
+
+    fn write(format: OutputFormat, options: WriterOptions, records: &[Value]) -> Result<File> {
+        let file = tempfile::tempfile()?;
+        let mut writer = ColumnarWriter::new(format, Box::new(file.try_clone()?), options);
+        writer.write_header(&StringRecord::from(vec!["name", "kit", "rating"]))?;
+        for record in records {
+            writer.write_record(record)?;
+        }
+        writer.finish()?;
+        Ok(file)
+    }
+
+    fn players() -> Vec<Value> {
+        vec![
+            json!({"name": "Buffon", "kit": 77, "rating": 88}),
+            json!({"name": "Dybala", "kit": null, "rating": 89.5}),
+            json!({"name": "Kean", "kit": 18, "rating": 70}),
+        ]
+    }
+
+    #[test]
+    fn test_parquet_row_groups_and_types() -> Result<()> {
+        let options = WriterOptions {
+            row_group_size: 2,
+            compression: Compression::Zstd,
+        };
+        let file = write(OutputFormat::Parquet, options, &players())?;
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+        assert_eq!(builder.metadata().num_row_groups(), 2);
+        let schema = builder.schema().clone();
+        assert_eq!(schema.field(0).data_type(), &DataType::Utf8);
+        assert_eq!(schema.field(1).data_type(), &DataType::Int64);
+        assert_eq!(schema.field(2).data_type(), &DataType::Float64);
+
+        let batches = builder.build()?.collect::<Result<Vec<_>, _>>()?;
+        let kits = batches[0].column(1).as_primitive::<Int64Type>();
+        assert_eq!((kits.value(0), kits.is_null(1)), (77, true));
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrow_ipc() -> Result<()> {
+        let file = write(OutputFormat::Arrow, WriterOptions::default(), &players())?;
+        let reader = FileReader::try_new(file, None)?;
+        let rows: usize = reader
+            .map(|batch| batch.map(|b| b.num_rows()))
+            .sum::<Result<_, _>>()?;
+        assert_eq!(rows, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_mismatch_after_first_batch() -> Result<()> {
+        let options = WriterOptions {
+            row_group_size: 1,
+            compression: Compression::None,
+        };
+        for format in [OutputFormat::Parquet, OutputFormat::Arrow] {
+            let file = tempfile::tempfile()?;
+            let mut writer =
+                ColumnarWriter::new(format, Box::new(file.try_clone()?), options.clone());
+            writer.write_header(&StringRecord::from(vec!["kit"]))?;
+            let result = [json!({"kit": 77}), json!({"kit": "x"})]
+                .iter()
+                .try_for_each(|record| writer.write_record(record))
+                .and_then(|_| writer.finish());
+            let err = result.unwrap_err();
+            assert!(err.to_string().contains("row 2: column \"kit\""), "{err}");
+            // 报错前没有任何字节写到输出
+            assert_eq!(file.metadata()?.len(), 0);
+        }
+        Ok(())
+    }
+}
@@ -1,62 +1,275 @@
-use std::{
-    fs::{File, OpenOptions},
-    path::Path,
-};
+use std::io::{Read, Write};
 
-use anyhow::Result;
-use csv::ReaderBuilder;
+use anyhow::{bail, Context, Result};
+use csv::StringRecord;
 use serde_json::Value;
 
-use crate::cli::csv::{CsvOpts, OutputFormat};
+use crate::{
+    cli::csv::CsvOpts,
+    utils::{default_output, get_writer},
+};
+
+use super::{
+    csv_filter::{Expr, Projection},
+    csv_from::Unflatten,
+    csv_parallel::{convert_csv_parallel, parallel_jobs, CHUNK_SIZE},
+    csv_reader::{csv_reader, open_source},
+    csv_types::Schema,
+    csv_writer::{record_writer_with, RecordWriter, WriterOptions},
+    spreadsheet::{is_spreadsheet, sheet_names},
+};
 
 pub fn process_csv(opts: CsvOpts) -> Result<()> {
     let CsvOpts {
-        input,
+        read,
         output,
         format,
-        header,
-        delimiter,
-    } = opts;
-    println!(
+        ..
+    } = &opts;
+    let input = &read.input;
+    if opts.list_sheets {
+        if !is_spreadsheet(input) {
+            bail!("--list-sheets only applies to spreadsheet input");
+        }
+        for name in sheet_names(input)? {
+            println!("{name}");
+        }
+        return Ok(());
+    }
+    // 诊断信息输出到 stderr，避免污染管道中的 stdout
+    eprintln!(
         "input: {}, output: {:?}, format: {:?}, header: {}, delimiter: {}",
-        input, output, format, header, delimiter
+        input, output, format, read.dialect.header, read.dialect.delimiter
     );
-    let input_path = Path::new(input.as_str());
-    let f = File::open(input_path)?;
+    // write records to file or stdout
+    let output = default_output(input, output.as_deref(), &format.to_string());
+    let rows = match parallel_jobs(&opts) {
+        Some(jobs) => convert_csv_parallel(&opts, jobs, CHUNK_SIZE, get_writer(&output)?)?,
+        None => {
+            let reader = open_source(read)?;
+            convert_csv(&opts, reader, get_writer(&output)?)?
+        }
+    };
 
-    let mut reader = ReaderBuilder::new()
-        .has_headers(header)
-        .delimiter(delimiter as u8)
-        .from_reader(f);
+    eprintln!("{input} -> {output}, {rows} rows, Done.");
 
-    // read headers
-    let headers = reader.headers()?.clone();
+    Ok(())
+}
+
+/// 流式转换：逐条读取 csv 记录并立即写出，返回处理的行数
+pub fn convert_csv(opts: &CsvOpts, input: impl Read, output: Box<dyn Write>) -> Result<usize> {
+    let (mut reader, headers) = csv_reader(&opts.read, input)?;
+    let converter = Converter::new(opts, &headers)?;
+    let mut writer = output_writer(opts, output)?;
+    writer.write_header(converter.headers())?;
 
     // read records
-    let mut records = Vec::with_capacity(128);
-    for result in reader.records() {
-        let record = result?;
-        let json_value = headers.iter().zip(record.iter()).collect::<Value>();
-        records.push(json_value);
+    let mut record = StringRecord::new();
+    let mut projected = StringRecord::new();
+    let mut rows = 0;
+    while reader.read_record(&mut record)? {
+        let converted = converter
+            .convert(&record, &mut projected)
+            .with_context(|| {
+                let line = record.position().map_or(0, |pos| pos.line());
+                format!("line {line}")
+            })?;
+        if let Some(json_value) = converted {
+            writer.write_record(&json_value)?;
+            rows += 1;
+        }
     }
+    writer.finish()?;
+
+    Ok(rows)
+}
 
-    // write records to file
-    let output = output.unwrap_or_else(|| input.clone() + format!(".{}", format).as_str());
-    let output_path = Path::new(output.as_str());
-    let output_file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(output_path)?;
+/// 按输出格式与列式参数创建写出器
+pub fn output_writer(opts: &CsvOpts, output: Box<dyn Write>) -> Result<Box<dyn RecordWriter>> {
+    let options = WriterOptions {
+        row_group_size: opts.row_group_size,
+        compression: opts.compression,
+    };
+    record_writer_with(&opts.format, output, &options)
+}
 
-    match format {
-        OutputFormat::Json => serde_json::to_writer_pretty(&output_file, &records)?,
-        OutputFormat::Yaml => serde_yaml::to_writer(&output_file, &records)?,
+/// 单条记录的转换：过滤、选列、类型转换与嵌套还原，顺序与并行转换共用
+pub struct Converter {
+    filter: Option<Expr>,
+    projection: Projection,
+    schema: Schema,
+    unflatten: Option<Unflatten>,
+}
+
+impl Converter {
+    pub fn new(opts: &CsvOpts, headers: &StringRecord) -> Result<Self> {
+        // 列式格式总是推断类型，否则所有列都会写成字符串
+        let infer = opts.infer_types || opts.format.is_columnar();
+        let schema = match &opts.schema {
+            Some(path) => Schema::load(path, infer)?,
+            None => Schema::new(Default::default(), infer),
+        };
+        if opts.unflatten && !opts.format.is_nested() {
+            bail!("--unflatten requires json, ndjson, yaml or toml output");
+        }
+        let filter = opts
+            .filter
+            .as_deref()
+            .map(|expr| Expr::parse(expr, headers))
+            .transpose()?;
+        let projection = Projection::new(headers, &opts.select, &opts.rename)?;
+        let unflatten = opts
+            .unflatten
+            .then(|| Unflatten::new(projection.headers().iter()));
+        Ok(Self {
+            filter,
+            projection,
+            schema,
+            unflatten,
+        })
     }
-    // serde_json::to_writer_pretty(&output_file, &records)?;
 
-    println!("{input} -> {output}, Done.");
+    /// 输出的列名
+    pub fn headers(&self) -> &StringRecord {
+        self.projection.headers()
+    }
 
-    Ok(())
+    /// 转换一条记录，被 `--where` 过滤掉时返回 `None`
+    pub fn convert(
+        &self,
+        record: &StringRecord,
+        projected: &mut StringRecord,
+    ) -> Result<Option<Value>> {
+        if self.filter.as_ref().is_some_and(|f| !f.matches(record)) {
+            return Ok(None);
+        }
+        self.projection.apply(record, projected);
+        let value = self.schema.record_to_value(self.headers(), projected)?;
+        Ok(Some(match (&self.unflatten, value) {
+            (Some(unflatten), Value::Object(map)) => unflatten.apply(map)?,
+            (_, value) => value,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::utils::SharedBuf;
+
+    fn convert(args: &[&str], input: &str) -> Result<String> {
+        convert_to("ndjson", args, input)
+    }
+
+    fn convert_to(format: &str, args: &[&str], input: &str) -> Result<String> {
+        let opts = CsvOpts::try_parse_from(["csv", "-i", "-", "-f", format].iter().chain(args))?;
+        let buf = SharedBuf::default();
+        convert_csv(&opts, input.as_bytes(), Box::new(buf.clone()))?;
+        buf.contents()
+    }
+
+    #[test]
+    fn test_convert_headerless() -> Result<()> {
+        let input = "Buffon,77\nDybala,10\n";
+        assert_eq!(
+            convert(&["--header", "false"], input)?,
+            "{\"col1\":\"Buffon\",\"col2\":\"77\"}\n{\"col1\":\"Dybala\",\"col2\":\"10\"}\n"
+        );
+        assert_eq!(
+            convert(&["--header", "false", "--columns", "name,kit"], input)?,
+            "{\"name\":\"Buffon\",\"kit\":\"77\"}\n{\"name\":\"Dybala\",\"kit\":\"10\"}\n"
+        );
+        assert!(convert(&["--header", "false", "--columns", "name"], input).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_select_where() -> Result<()> {
+        let input =
+            "Name,Nationality,Kit Number\nBuffon,Italy,77\nRonaldo,Portugal,7\nBonucci,Italy,19\n";
+        assert_eq!(
+            convert(
+                &[
+                    "--select",
+                    "Name,Kit Number",
+                    "--rename",
+                    "Kit Number=kit",
+                    "--where",
+                    "Nationality == 'Italy' && `Kit Number` < 50",
+                ],
+                input
+            )?,
+            "{\"Name\":\"Bonucci\",\"kit\":\"19\"}\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_duplicate_headers() {
+        let message = |result: Result<String>| result.unwrap_err().to_string();
+        for format in ["csv", "json", "ndjson"] {
+            let err = message(convert_to(format, &[], "a,b,a\n1,2,3\n"));
+            assert!(
+                err.contains("duplicate column name \"a\""),
+                "{format}: {err}"
+            );
+        }
+        let err = message(convert(&["--rename", "a=b"], "a,b\n1,2\n"));
+        assert!(err.contains("after --select/--rename"), "{err}");
+        let err = message(convert(&["--select", "a,a"], "a,b\n1,2\n"));
+        assert!(err.contains("after --select/--rename"), "{err}");
+    }
+
+    #[test]
+    fn test_convert_rename_header() -> Result<()> {
+        let input = "Name,Kit Number\nBuffon,77\n";
+        assert_eq!(
+            convert(&["--columns", "name,kit"], input)?,
+            "{\"name\":\"Buffon\",\"kit\":\"77\"}\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_unflatten() -> Result<()> {
+        let input = "name,address.city,tags[0],tags[1]\nDybala,Turin,fw,10\n";
+        assert_eq!(
+            convert(&["--unflatten", "--infer-types"], input)?,
+            "{\"name\":\"Dybala\",\"address\":{\"city\":\"Turin\"},\"tags\":[\"fw\",10]}\n"
+        );
+        let err = convert_to("csv", &["--unflatten"], input).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("--unflatten requires json, ndjson, yaml or toml output"),
+            "{err}"
+        );
+        assert!(convert(&["--unflatten"], "a,a.b\n1,2\n").is_err());
+
+        // 空单元格在 toml 中省略，数组中保留为空字符串
+        let input = "name,address.city,tags[0],tags[1]\nDybala,,fw,\n";
+        let out = convert_to("toml", &["--unflatten", "--infer-types"], input)?;
+        let record = &toml::from_str::<Value>(&out)?["records"][0];
+        assert_eq!(record["address"], serde_json::json!({}));
+        assert_eq!(record["tags"], serde_json::json!(["fw", ""]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_header_only() -> Result<()> {
+        for format in ["json", "yaml", "toml"] {
+            let opts = CsvOpts::try_parse_from(["csv", "-i", "-", "-f", format])?;
+            let buf = SharedBuf::default();
+            convert_csv(&opts, "name,club\n".as_bytes(), Box::new(buf.clone()))?;
+            let out = buf.contents()?;
+            let records = match format {
+                "json" => serde_json::from_str::<Value>(&out)?,
+                "yaml" => serde_yaml::from_str::<Value>(&out)?,
+                _ => toml::from_str::<Value>(&out)?["records"].clone(),
+            };
+            assert_eq!(records, Value::Array(vec![]), "{format}: {out:?}");
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,93 @@
+use std::{collections::HashSet, io::Read, io::Write};
+
+use anyhow::Result;
+use csv::{Reader, StringRecord};
+
+use crate::{
+    cli::csv::CsvDedupeOpts,
+    utils::{default_output, get_writer},
+};
+
+use super::{
+    csv_filter::resolve_columns, csv_reader::open_csv, csv_types::Schema, csv_writer::record_writer,
+};
+
+pub fn process_csv_dedupe(opts: CsvDedupeOpts) -> Result<()> {
+    let (reader, headers) = open_csv(&opts.read)?;
+    let input = &opts.read.input;
+    let output = default_output(input, opts.output.as_deref(), &opts.format.to_string());
+    let (rows, removed) = dedupe_csv(&opts, reader, &headers, get_writer(&output)?)?;
+    eprintln!("{input} -> {output}, {rows} rows, {removed} duplicates removed, Done.");
+
+    Ok(())
+}
+
+/// 流式去重，保留每个键首次出现的行，返回 (输出行数, 去除行数)
+pub fn dedupe_csv<R: Read>(
+    opts: &CsvDedupeOpts,
+    mut reader: Reader<R>,
+    headers: &StringRecord,
+    output: Box<dyn Write>,
+) -> Result<(usize, usize)> {
+    let indices = if opts.by.is_empty() {
+        (0..headers.len()).collect()
+    } else {
+        resolve_columns(headers, &opts.by, "--by")?
+    };
+
+    let schema = Schema::new(Default::default(), false);
+    let mut writer = record_writer(&opts.format, output)?;
+    writer.write_header(headers)?;
+
+    let mut seen = HashSet::new();
+    let (mut rows, mut removed) = (0, 0);
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        let key: Vec<String> = indices
+            .iter()
+            .map(|&i| record.get(i).unwrap_or_default().to_string())
+            .collect();
+        if !seen.insert(key) {
+            removed += 1;
+            continue;
+        }
+        writer.write_record(&schema.record_to_value(headers, &record)?)?;
+        rows += 1;
+    }
+    writer.finish()?;
+
+    Ok((rows, removed))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::{process::csv_reader::csv_reader, utils::SharedBuf};
+
+    fn dedupe(args: &[&str], input: &str) -> Result<(String, usize)> {
+        let opts = CsvDedupeOpts::try_parse_from(["dedupe", "-i", "-"].iter().chain(args))?;
+        let (reader, headers) = csv_reader(&opts.read, input.as_bytes())?;
+        let buf = SharedBuf::default();
+        let (_, removed) = dedupe_csv(&opts, reader, &headers, Box::new(buf.clone()))?;
+        Ok((buf.contents()?, removed))
+    }
+
+    #[test]
+    fn test_dedupe() -> Result<()> {
+        let input = "name,club\nBuffon,Juventus\nBuffon,PSG\nBuffon,Juventus\nDybala,Juventus\n";
+        assert_eq!(
+            dedupe(&[], input)?,
+            (
+                "name,club\nBuffon,Juventus\nBuffon,PSG\nDybala,Juventus\n".to_string(),
+                1
+            )
+        );
+        assert_eq!(
+            dedupe(&["--by", "club"], input)?,
+            ("name,club\nBuffon,Juventus\nBuffon,PSG\n".to_string(), 2)
+        );
+        Ok(())
+    }
+}
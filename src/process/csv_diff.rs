@@ -0,0 +1,333 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Read, Write},
+};
+
+use anyhow::{bail, Result};
+use csv::{Reader, StringRecord};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    cli::csv::{CsvDiffOpts, DiffFormat},
+    utils::get_writer,
+};
+
+use super::{
+    csv_filter::resolve_columns,
+    csv_reader::{dialect_reader, open_input},
+};
+
+/// 两个文件按主键比较的结果，新增与修改按新文件顺序排列，删除按旧文件顺序排列
+#[derive(Debug, Default, Serialize)]
+pub struct DiffReport {
+    pub columns: ColumnChanges,
+    pub added: Vec<RowEntry>,
+    pub removed: Vec<RowEntry>,
+    pub changed: Vec<RowChange>,
+    pub unchanged: usize,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ColumnChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowEntry {
+    pub key: Map<String, Value>,
+    pub row: Map<String, Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowChange {
+    pub key: Map<String, Value>,
+    pub changes: Vec<CellChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CellChange {
+    pub column: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl DiffReport {
+    pub fn is_empty(&self) -> bool {
+        self.columns.added.is_empty()
+            && self.columns.removed.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+
+    /// 可读的文本报告：`+` 新增，`-` 删除，`~` 修改
+    pub fn write_text(&self, writer: &mut impl Write) -> Result<()> {
+        if !self.columns.added.is_empty() {
+            writeln!(writer, "columns added: {}", self.columns.added.join(", "))?;
+        }
+        if !self.columns.removed.is_empty() {
+            writeln!(
+                writer,
+                "columns removed: {}",
+                self.columns.removed.join(", ")
+            )?;
+        }
+        for entry in &self.added {
+            writeln!(writer, "+ {}: {}", pairs(&entry.key), pairs(&entry.row))?;
+        }
+        for entry in &self.removed {
+            writeln!(writer, "- {}: {}", pairs(&entry.key), pairs(&entry.row))?;
+        }
+        for change in &self.changed {
+            writeln!(writer, "~ {}", pairs(&change.key))?;
+            for cell in &change.changes {
+                writeln!(
+                    writer,
+                    "    {}: {:?} -> {:?}",
+                    cell.column, cell.old, cell.new
+                )?;
+            }
+        }
+        writeln!(
+            writer,
+            "{} added, {} removed, {} changed, {} unchanged",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len(),
+            self.unchanged
+        )?;
+        Ok(())
+    }
+}
+
+fn pairs(map: &Map<String, Value>) -> String {
+    map.iter()
+        .map(|(k, v)| format!("{k}={}", v.as_str().unwrap_or_default()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// 与 `diff` 一致，有差异时返回错误，进程以非零状态退出
+pub fn process_csv_diff(opts: CsvDiffOpts) -> Result<()> {
+    let (old, old_headers) = dialect_reader(&opts.dialect, &[], open_input(&opts.old)?)?;
+    let (new, new_headers) = dialect_reader(&opts.dialect, &[], open_input(&opts.new)?)?;
+    let report = diff_csv(&opts.key, (old, &old_headers), (new, &new_headers))?;
+
+    let mut writer = get_writer(opts.output.as_deref().unwrap_or("-"))?;
+    match opts.format {
+        DiffFormat::Text => report.write_text(&mut writer)?,
+        DiffFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &report)?;
+            writeln!(writer)?;
+        }
+    }
+    writer.flush()?;
+    if !report.is_empty() {
+        bail!("{} and {} differ", opts.old, opts.new);
+    }
+
+    Ok(())
+}
+
+/// 按 `key` 列比较两个文件；键在同一文件中重复时报错
+pub fn diff_csv<A: Read, B: Read>(
+    key: &[String],
+    (mut old, old_headers): (Reader<A>, &StringRecord),
+    (mut new, new_headers): (Reader<B>, &StringRecord),
+) -> Result<DiffReport> {
+    let old_key = resolve_columns(old_headers, key, "--key")?;
+    let new_key = resolve_columns(new_headers, key, "--key")?;
+
+    // 旧文件整体读入内存，新文件逐行比较
+    let mut rows = Vec::new();
+    let mut index = HashMap::new();
+    for record in old.records() {
+        let record = record?;
+        let id = key_of(&record, &old_key);
+        if index.insert(id.clone(), rows.len()).is_some() {
+            bail!("old file: duplicate key {id:?}");
+        }
+        rows.push(record);
+    }
+
+    let mut report = DiffReport {
+        columns: ColumnChanges {
+            added: missing(new_headers, old_headers),
+            removed: missing(old_headers, new_headers),
+        },
+        ..Default::default()
+    };
+    // 两侧都有的列，按新文件顺序比较
+    let common: Vec<(usize, usize, &str)> = new_headers
+        .iter()
+        .enumerate()
+        .filter_map(|(j, name)| {
+            let i = old_headers.iter().position(|h| h == name)?;
+            Some((i, j, name))
+        })
+        .collect();
+
+    let mut matched = vec![false; rows.len()];
+    let mut seen = HashSet::new();
+    for record in new.records() {
+        let record = record?;
+        let id = key_of(&record, &new_key);
+        if !seen.insert(id.clone()) {
+            bail!("new file: duplicate key {id:?}");
+        }
+        let Some(&i) = index.get(&id) else {
+            report.added.push(RowEntry {
+                key: key_map(key, &id),
+                row: row_map(new_headers, &record),
+            });
+            continue;
+        };
+        matched[i] = true;
+        let changes: Vec<CellChange> = common
+            .iter()
+            .filter_map(|&(oi, ni, name)| {
+                let (a, b) = (
+                    rows[i].get(oi).unwrap_or_default(),
+                    record.get(ni).unwrap_or_default(),
+                );
+                (a != b).then(|| CellChange {
+                    column: name.to_string(),
+                    old: a.to_string(),
+                    new: b.to_string(),
+                })
+            })
+            .collect();
+        if changes.is_empty() {
+            report.unchanged += 1;
+        } else {
+            report.changed.push(RowChange {
+                key: key_map(key, &id),
+                changes,
+            });
+        }
+    }
+
+    for (record, _) in rows.iter().zip(matched).filter(|(_, m)| !m) {
+        report.removed.push(RowEntry {
+            key: key_map(key, &key_of(record, &old_key)),
+            row: row_map(old_headers, record),
+        });
+    }
+
+    Ok(report)
+}
+
+fn key_of(record: &StringRecord, indices: &[usize]) -> Vec<String> {
+    indices
+        .iter()
+        .map(|&i| record.get(i).unwrap_or_default().to_string())
+        .collect()
+}
+
+fn key_map(names: &[String], id: &[String]) -> Map<String, Value> {
+    names
+        .iter()
+        .cloned()
+        .zip(id.iter().cloned().map(Value::String))
+        .collect()
+}
+
+fn row_map(headers: &StringRecord, record: &StringRecord) -> Map<String, Value> {
+    headers
+        .iter()
+        .zip(record.iter())
+        .map(|(h, v)| (h.to_string(), Value::String(v.to_string())))
+        .collect()
+}
+
+/// `a` 中有而 `b` 中没有的列
+fn missing(a: &StringRecord, b: &StringRecord) -> Vec<String> {
+    a.iter()
+        .filter(|name| !b.iter().any(|h| h == *name))
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn diff(key: &[&str], old: &str, new: &str) -> Result<DiffReport> {
+        let key: Vec<String> = key.iter().map(|k| k.to_string()).collect();
+        let mut old = csv::Reader::from_reader(old.as_bytes());
+        let mut new = csv::Reader::from_reader(new.as_bytes());
+        let (old_headers, new_headers) = (old.headers()?.clone(), new.headers()?.clone());
+        diff_csv(&key, (old, &old_headers), (new, &new_headers))
+    }
+
+    #[test]
+    fn test_diff_report() -> Result<()> {
+        let old =
+            "id,name,kit,club\n1,Buffon,77,Juventus\n2,Pogba,6,Juventus\n3,Dybala,21,Juventus\n";
+        let new = "id,kit,name,age\n3,10,Dybala,25\n1,77,Buffon,41\n4,18,Kean,19\n";
+        let report = diff(&["id"], old, new)?;
+        let mut text = Vec::new();
+        report.write_text(&mut text)?;
+        assert_eq!(
+            String::from_utf8(text)?,
+            "columns added: age\n\
+             columns removed: club\n\
+             + id=4: id=4, kit=18, name=Kean, age=19\n\
+             - id=2: id=2, name=Pogba, kit=6, club=Juventus\n\
+             ~ id=3\n    kit: \"21\" -> \"10\"\n\
+             1 added, 1 removed, 1 changed, 1 unchanged\n"
+        );
+
+        let json = serde_json::to_value(&report)?;
+        assert_eq!(
+            json["changed"][0],
+            serde_json::json!({
+                "key": {"id": "3"},
+                "changes": [{"column": "kit", "old": "21", "new": "10"}]
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_composite_key_and_errors() -> Result<()> {
+        let old = "club,kit,name\nJuve,10,Dybala\nRoma,10,Totti\n";
+        let new = "club,kit,name\nRoma,10,Totti\nJuve,10,Dybala\n";
+        assert!(diff(&["club", "kit"], old, new)?.is_empty());
+        assert!(diff(&["kit"], old, new).is_err());
+        assert!(diff(&["id"], old, new).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_exit_status() -> Result<()> {
+        let write = |content: &str| -> Result<tempfile::NamedTempFile> {
+            let mut file = tempfile::Builder::new().suffix(".csv").tempfile()?;
+            file.write_all(content.as_bytes())?;
+            Ok(file)
+        };
+        let old = write("id,kit\n1,77\n")?;
+        let same = write("id,kit\n1,77\n")?;
+        let changed = write("id,kit\n1,1\n")?;
+        let report = tempfile::NamedTempFile::new()?;
+        let run = |new: &tempfile::NamedTempFile| {
+            process_csv_diff(CsvDiffOpts::try_parse_from([
+                "diff",
+                &old.path().to_string_lossy(),
+                &new.path().to_string_lossy(),
+                "--key",
+                "id",
+                "-o",
+                &report.path().to_string_lossy(),
+            ])?)
+        };
+        assert!(run(&same).is_ok());
+        assert!(run(&changed).is_err());
+        assert!(std::fs::read_to_string(report.path())?.contains("1 changed"));
+        Ok(())
+    }
+}
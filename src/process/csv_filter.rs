@@ -0,0 +1,347 @@
+use std::{cmp::Ordering, iter::Peekable, str::Chars};
+
+use anyhow::{anyhow, bail, Result};
+use csv::StringRecord;
+
+use super::csv_reader::check_unique_headers;
+
+/// 行过滤表达式，例如 `age > 30 && nationality == "Italy"`
+///
+/// 支持 `== != > >= < <=`、`&& || !` 与括号；列名含空格时用反引号包裹，
+/// 如 `` `Kit Number` >= 10 ``。两侧均为数字时按数值比较，否则按字符串比较。
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Cmp(Operand, CmpOp, Operand),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Column(usize),
+    Literal(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Literal(String),
+    Cmp(CmpOp),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+impl Expr {
+    /// 解析表达式并将列名解析为 `headers` 中的下标
+    pub fn parse(input: &str, headers: &StringRecord) -> Result<Self> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser {
+            tokens: tokens.into_iter().peekable(),
+            headers,
+        };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.tokens.next() {
+            bail!("unexpected token {:?} in filter expression", token);
+        }
+        Ok(expr)
+    }
+
+    pub fn matches(&self, record: &StringRecord) -> bool {
+        match self {
+            Expr::Cmp(lhs, op, rhs) => {
+                let ordering = compare(lhs.resolve(record), rhs.resolve(record));
+                match op {
+                    CmpOp::Eq => ordering == Ordering::Equal,
+                    CmpOp::Ne => ordering != Ordering::Equal,
+                    CmpOp::Gt => ordering == Ordering::Greater,
+                    CmpOp::Ge => ordering != Ordering::Less,
+                    CmpOp::Lt => ordering == Ordering::Less,
+                    CmpOp::Le => ordering != Ordering::Greater,
+                }
+            }
+            Expr::And(lhs, rhs) => lhs.matches(record) && rhs.matches(record),
+            Expr::Or(lhs, rhs) => lhs.matches(record) || rhs.matches(record),
+            Expr::Not(expr) => !expr.matches(record),
+        }
+    }
+}
+
+impl Operand {
+    fn resolve<'a>(&'a self, record: &'a StringRecord) -> &'a str {
+        match self {
+            Operand::Column(idx) => record.get(*idx).unwrap_or_default(),
+            Operand::Literal(s) => s,
+        }
+    }
+}
+
+fn compare(lhs: &str, rhs: &str) -> Ordering {
+    match (lhs.trim().parse::<f64>(), rhs.trim().parse::<f64>()) {
+        (Ok(l), Ok(r)) => l.partial_cmp(&r).unwrap_or(Ordering::Equal),
+        _ => lhs.cmp(rhs),
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            '"' | '\'' => tokens.push(Token::Literal(read_quoted(&mut chars)?)),
+            '`' => tokens.push(Token::Ident(read_quoted(&mut chars)?)),
+            '&' | '|' => {
+                chars.next();
+                if chars.next() != Some(c) {
+                    bail!("expected `{c}{c}` in filter expression");
+                }
+                tokens.push(if c == '&' { Token::And } else { Token::Or });
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let eq = chars.next_if_eq(&'=').is_some();
+                tokens.push(match (c, eq) {
+                    ('=', true) => Token::Cmp(CmpOp::Eq),
+                    ('!', true) => Token::Cmp(CmpOp::Ne),
+                    ('!', false) => Token::Not,
+                    ('<', true) => Token::Cmp(CmpOp::Le),
+                    ('<', false) => Token::Cmp(CmpOp::Lt),
+                    ('>', true) => Token::Cmp(CmpOp::Ge),
+                    ('>', false) => Token::Cmp(CmpOp::Gt),
+                    _ => bail!("expected `==` in filter expression"),
+                });
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let mut num = String::new();
+                while let Some(c) =
+                    chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '.' || *c == '-')
+                {
+                    num.push(c);
+                }
+                tokens.push(Token::Literal(num));
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_' || *c == '.')
+                {
+                    ident.push(c);
+                }
+                tokens.push(Token::Ident(ident));
+            }
+            c => bail!("unexpected character {c:?} in filter expression"),
+        }
+    }
+    Ok(tokens)
+}
+
+fn read_quoted(chars: &mut Peekable<Chars>) -> Result<String> {
+    let quote = chars.next().unwrap_or_default();
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            Some('\\') => s.extend(chars.next()),
+            Some(c) if c == quote => return Ok(s),
+            Some(c) => s.push(c),
+            None => bail!("unterminated {quote} in filter expression"),
+        }
+    }
+}
+
+struct Parser<'a, I: Iterator<Item = Token>> {
+    tokens: Peekable<I>,
+    headers: &'a StringRecord,
+}
+
+impl<I: Iterator<Item = Token>> Parser<'_, I> {
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.tokens.next_if_eq(&Token::Or).is_some() {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_unary()?;
+        while self.tokens.next_if_eq(&Token::And).is_some() {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        if self.tokens.next_if_eq(&Token::Not).is_some() {
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        if self.tokens.next_if_eq(&Token::LParen).is_some() {
+            let expr = self.parse_or()?;
+            if self.tokens.next() != Some(Token::RParen) {
+                bail!("missing `)` in filter expression");
+            }
+            return Ok(expr);
+        }
+        let lhs = self.parse_operand()?;
+        let op = match self.tokens.next() {
+            Some(Token::Cmp(op)) => op,
+            token => bail!("expected comparison operator, found {:?}", token),
+        };
+        let rhs = self.parse_operand()?;
+        Ok(Expr::Cmp(lhs, op, rhs))
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand> {
+        match self.tokens.next() {
+            Some(Token::Ident(name)) => self
+                .headers
+                .iter()
+                .position(|h| h == name)
+                .map(Operand::Column)
+                .ok_or_else(|| anyhow!("unknown column {name:?} in filter expression")),
+            Some(Token::Literal(s)) => Ok(Operand::Literal(s)),
+            token => bail!("expected column or value, found {:?}", token),
+        }
+    }
+}
+
+/// 列选择与重命名：`select` 为空时保留全部列，`rename` 形如 `old=new`
+#[derive(Debug)]
+pub struct Projection {
+    indices: Vec<usize>,
+    headers: StringRecord,
+}
+
+impl Projection {
+    pub fn new(headers: &StringRecord, select: &[String], rename: &[String]) -> Result<Self> {
+        let indices = if select.is_empty() {
+            (0..headers.len()).collect()
+        } else {
+            resolve_columns(headers, select, "--select")?
+        };
+
+        let mut names: Vec<String> = indices.iter().map(|&i| headers[i].to_string()).collect();
+        for pair in rename {
+            let (old, new) = pair
+                .split_once('=')
+                .ok_or_else(|| anyhow!("--rename expects old=new, got {pair:?}"))?;
+            let name = names
+                .iter_mut()
+                .find(|name| *name == old)
+                .ok_or_else(|| anyhow!("unknown column {old:?} in --rename"))?;
+            *name = new.to_string();
+        }
+        check_unique_headers(names.iter().map(String::as_str), "after --select/--rename")?;
+
+        Ok(Self {
+            indices,
+            headers: StringRecord::from(names),
+        })
+    }
+
+    pub fn headers(&self) -> &StringRecord {
+        &self.headers
+    }
+
+    pub fn apply(&self, record: &StringRecord, out: &mut StringRecord) {
+        out.clear();
+        for &i in &self.indices {
+            out.push_field(record.get(i).unwrap_or_default());
+        }
+    }
+}
+
+/// 将列名解析为 `headers` 中的下标，`option` 用于错误信息
+pub fn resolve_columns(
+    headers: &StringRecord,
+    names: &[String],
+    option: &str,
+) -> Result<Vec<usize>> {
+    names
+        .iter()
+        .map(|name| {
+            headers
+                .iter()
+                .position(|h| h == name)
+                .ok_or_else(|| anyhow!("unknown column {name:?} in {option}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> StringRecord {
+        StringRecord::from(vec!["Name", "Nationality", "Kit Number", "age"])
+    }
+
+    #[test]
+    fn test_filter_expression() -> Result<()> {
+        let headers = headers();
+        let expr = Expr::parse(r#"age > 30 && Nationality == "Italy""#, &headers)?;
+        assert!(expr.matches(&StringRecord::from(vec!["Buffon", "Italy", "77", "41"])));
+        assert!(!expr.matches(&StringRecord::from(vec!["Rugani", "Italy", "24", "25"])));
+        assert!(!expr.matches(&StringRecord::from(vec!["Ronaldo", "Portugal", "7", "34"])));
+
+        let expr = Expr::parse("!(`Kit Number` < 10 || Name == 'Danilo')", &headers)?;
+        assert!(expr.matches(&StringRecord::from(vec!["Buffon", "Italy", "77", "41"])));
+        assert!(!expr.matches(&StringRecord::from(vec!["Ronaldo", "Portugal", "7", "34"])));
+        assert!(!expr.matches(&StringRecord::from(vec!["Danilo", "Brazil", "13", "28"])));
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_expression_errors() {
+        let headers = headers();
+        assert!(Expr::parse("club == 'Juventus'", &headers).is_err());
+        assert!(Expr::parse("age >", &headers).is_err());
+        assert!(Expr::parse("(age > 1", &headers).is_err());
+        assert!(Expr::parse("age = 1", &headers).is_err());
+    }
+
+    #[test]
+    fn test_projection() -> Result<()> {
+        let projection = Projection::new(
+            &headers(),
+            &["Kit Number".into(), "Name".into()],
+            &["Kit Number=kit".into()],
+        )?;
+        assert_eq!(
+            projection.headers(),
+            &StringRecord::from(vec!["kit", "Name"])
+        );
+
+        let mut out = StringRecord::new();
+        projection.apply(
+            &StringRecord::from(vec!["Buffon", "Italy", "77", "41"]),
+            &mut out,
+        );
+        assert_eq!(out, StringRecord::from(vec!["77", "Buffon"]));
+
+        assert!(Projection::new(&headers(), &["club".into()], &[]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,298 @@
+use std::{collections::HashSet, io::Write, path::Path};
+
+use anyhow::{anyhow, bail, Result};
+use csv::WriterBuilder;
+use serde_json::{Map, Value};
+
+use crate::{
+    cli::csv::{CsvFromOpts, OutputFormat},
+    utils::{default_output, get_writer, read_input},
+};
+
+pub fn process_csv_from(opts: CsvFromOpts) -> Result<()> {
+    let format = match &opts.format {
+        Some(format) => format.clone(),
+        None => detect_format(&opts.input)?,
+    };
+    let mut buf = Vec::new();
+    read_input(&opts.input, &mut buf)?;
+    let records = parse_records(&String::from_utf8(buf)?, &format)?;
+
+    let output = default_output(&opts.input, opts.output.as_deref(), "csv");
+    let rows = write_flattened(&records, opts.delimiter as u8, get_writer(&output)?)?;
+    eprintln!("{} -> {output}, {rows} rows, Done.", opts.input);
+
+    Ok(())
+}
+
+fn detect_format(input: &str) -> Result<OutputFormat> {
+    let ext = Path::new(input)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    match ext {
+        "json" => Ok(OutputFormat::Json),
+        "yaml" | "yml" => Ok(OutputFormat::Yaml),
+        "toml" => Ok(OutputFormat::Toml),
+        "ndjson" | "jsonl" => Ok(OutputFormat::Ndjson),
+        _ => bail!("cannot detect input format of {input:?}, please specify --format"),
+    }
+}
+
+/// 解析为记录列表：顶层数组的每个元素为一条记录；toml 取顶层第一个数组
+pub fn parse_records(content: &str, format: &OutputFormat) -> Result<Vec<Value>> {
+    let value: Value = match format {
+        OutputFormat::Json => serde_json::from_str(content)?,
+        OutputFormat::Yaml => serde_yaml::from_str(content)?,
+        OutputFormat::Toml => {
+            let table: Map<String, Value> = toml::from_str(content)?;
+            table
+                .into_iter()
+                .map(|(_, v)| v)
+                .find(Value::is_array)
+                .ok_or_else(|| anyhow!("toml input has no array of tables"))?
+        }
+        OutputFormat::Ndjson => Value::Array(
+            content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str)
+                .collect::<Result<_, _>>()?,
+        ),
+        OutputFormat::Tsv | OutputFormat::Csv | OutputFormat::Parquet | OutputFormat::Arrow => {
+            bail!("{format} is not a record input format")
+        }
+    };
+
+    Ok(match value {
+        Value::Array(records) => records,
+        value => vec![value],
+    })
+}
+
+/// 展开嵌套结构：对象键用 `.` 连接，数组下标写作 `[i]`
+pub fn flatten(value: &Value) -> Map<String, Value> {
+    let mut map = Map::new();
+    flatten_into(String::new(), value, &mut map);
+    map
+}
+
+fn flatten_into(prefix: String, value: &Value, map: &mut Map<String, Value>) {
+    match value {
+        Value::Object(obj) if !obj.is_empty() => {
+            for (k, v) in obj {
+                let key = if prefix.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{prefix}.{k}")
+                };
+                flatten_into(key, v, map);
+            }
+        }
+        Value::Array(arr) if !arr.is_empty() => {
+            for (i, v) in arr.iter().enumerate() {
+                flatten_into(format!("{prefix}[{i}]"), v, map);
+            }
+        }
+        v => {
+            map.insert(prefix, v.clone());
+        }
+    }
+}
+
+/// 列名中的一段路径：对象键或数组下标
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// `flatten` 的逆操作：按列名中的 `.` 与 `[i]` 还原嵌套对象与数组
+#[derive(Debug)]
+pub struct Unflatten {
+    paths: Vec<Vec<Segment>>,
+}
+
+impl Unflatten {
+    pub fn new<'a>(headers: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            paths: headers.into_iter().map(parse_path).collect(),
+        }
+    }
+
+    /// 将扁平记录转换为嵌套结构，键与表头按位置对应
+    pub fn apply(&self, record: Map<String, Value>) -> Result<Value> {
+        let mut root = Value::Object(Map::new());
+        for (path, (key, value)) in self.paths.iter().zip(record) {
+            if !insert(&mut root, path, value) {
+                bail!("column {key:?} conflicts with another nested column");
+            }
+        }
+        Ok(root)
+    }
+}
+
+/// 解析 `a.b[0].c`；无法解析的部分（如 `price[USD]`）按普通字符处理
+fn parse_path(header: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut name = String::new();
+    let mut chars = header.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => segments.push(Segment::Key(std::mem::take(&mut name))),
+            '[' => {
+                let rest: String = chars.clone().take_while(|c| *c != ']').collect();
+                match rest.parse::<usize>() {
+                    Ok(i) if chars.clone().nth(rest.len()) == Some(']') => {
+                        if !name.is_empty() || segments.is_empty() {
+                            segments.push(Segment::Key(std::mem::take(&mut name)));
+                        }
+                        segments.push(Segment::Index(i));
+                        chars.nth(rest.len());
+                        // 下标后紧跟的 `.` 只是分隔符
+                        chars.next_if_eq(&'.');
+                    }
+                    _ => name.push(c),
+                }
+            }
+            c => name.push(c),
+        }
+    }
+    if !name.is_empty() || !matches!(segments.last(), Some(Segment::Index(_))) {
+        segments.push(Segment::Key(name));
+    }
+    // 出现空键（如 `a..b`、`.a`）时不展开
+    if segments
+        .iter()
+        .any(|s| matches!(s, Segment::Key(k) if k.is_empty()))
+    {
+        return vec![Segment::Key(header.to_string())];
+    }
+    segments
+}
+
+/// 按路径写入值，路径与已有结构冲突时返回 false；数组中跳过的下标补 null
+fn insert(target: &mut Value, path: &[Segment], value: Value) -> bool {
+    let Some((segment, rest)) = path.split_first() else {
+        if target.is_null() {
+            *target = value;
+            return true;
+        }
+        return false;
+    };
+    if target.is_null() {
+        *target = match segment {
+            Segment::Key(_) => Value::Object(Map::new()),
+            Segment::Index(_) => Value::Array(Vec::new()),
+        };
+    }
+    let slot = match (segment, target) {
+        (Segment::Key(key), Value::Object(map)) => {
+            if rest.is_empty() && map.contains_key(key) {
+                return false;
+            }
+            map.entry(key.clone()).or_insert(Value::Null)
+        }
+        (Segment::Index(i), Value::Array(arr)) => {
+            if arr.len() <= *i {
+                arr.resize(*i + 1, Value::Null);
+            }
+            &mut arr[*i]
+        }
+        _ => return false,
+    };
+    insert(slot, rest, value)
+}
+
+/// 以所有记录键的并集（按首次出现顺序）作为表头写出 csv，返回行数
+pub fn write_flattened(records: &[Value], delimiter: u8, output: impl Write) -> Result<usize> {
+    let rows: Vec<Map<String, Value>> = records.iter().map(flatten).collect();
+
+    let mut seen = HashSet::new();
+    let headers: Vec<&String> = rows
+        .iter()
+        .flat_map(|row| row.keys())
+        .filter(|key| seen.insert(*key))
+        .collect();
+
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(output);
+    writer.write_record(&headers)?;
+    for row in &rows {
+        for key in &headers {
+            match row.get(*key) {
+                Some(Value::String(s)) => writer.write_field(s)?,
+                Some(Value::Null) | None => writer.write_field("")?,
+                Some(v) => writer.write_field(v.to_string())?,
+            }
+        }
+        writer.write_record(None::<&[u8]>)?;
+    }
+    writer.flush()?;
+
+    Ok(rows.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_flatten() {
+        let value = json!({"name": "Dybala", "address": {"city": "Turin"}, "tags": ["a", "b"]});
+        let map = flatten(&value);
+        assert_eq!(
+            map.keys().collect::<Vec<_>>(),
+            ["name", "address.city", "tags[0]", "tags[1]"]
+        );
+    }
+
+    #[test]
+    fn test_unflatten() -> Result<()> {
+        let value = json!({
+            "name": "Dybala",
+            "address": {"city": "Turin", "geo": {"lat": 45.07}},
+            "tags": ["a", null, "c"],
+            "matrix": [[1, 2]],
+            "clubs": [{"name": "Palermo"}, {"name": "Juventus"}],
+            "price[USD]": 10
+        });
+        let flat = flatten(&value);
+        let unflatten = Unflatten::new(flat.keys().map(String::as_str));
+        assert_eq!(unflatten.apply(flat)?, value);
+
+        let headers = ["a", "a.b"];
+        let record = headers.iter().map(|h| (h.to_string(), json!(1))).collect();
+        assert!(Unflatten::new(headers).apply(record).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_flattened_header_union() -> Result<()> {
+        let records = parse_records(
+            "[{\"name\": \"Buffon\", \"kit\": 77}, {\"name\": \"Dybala\", \"club\": {\"name\": \"Juventus\"}}]",
+            &OutputFormat::Json,
+        )?;
+        let mut buf = Vec::new();
+        assert_eq!(write_flattened(&records, b',', &mut buf)?, 2);
+        assert_eq!(
+            String::from_utf8(buf)?,
+            "name,kit,club.name\nBuffon,77,\nDybala,,Juventus\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_toml_records() -> Result<()> {
+        let records = parse_records(
+            "[[records]]\nname = \"Buffon\"\n\n[[records]]\nname = \"Dybala\"\n",
+            &OutputFormat::Toml,
+        )?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1]["name"], json!("Dybala"));
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use csv::{Reader, StringRecord};
+use serde_json::{Map, Value};
+
+use crate::{
+    cli::csv::CsvGroupByOpts,
+    utils::{default_output, get_writer},
+};
+
+use super::{csv_filter::resolve_columns, csv_reader::open_csv, csv_writer::record_writer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggFunc {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+/// 单个聚合项，`column` 为 `None` 时统计行数，否则只统计非空单元格
+#[derive(Debug)]
+pub struct Aggregate {
+    func: AggFunc,
+    column: Option<usize>,
+    name: String,
+}
+
+#[derive(Debug, Clone)]
+struct AggState {
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Aggregate {
+    /// 解析 `count`、`count:col`、`sum:col` 等形式
+    pub fn parse(spec: &str, headers: &StringRecord) -> Result<Self> {
+        let (name, column) = match spec.split_once(':') {
+            Some((name, column)) => (name, Some(column)),
+            None => (spec, None),
+        };
+        let func = match name {
+            "count" => AggFunc::Count,
+            "sum" => AggFunc::Sum,
+            "avg" | "mean" => AggFunc::Avg,
+            "min" => AggFunc::Min,
+            "max" => AggFunc::Max,
+            _ => bail!("unknown aggregate {name:?}, expected count/sum/avg/min/max"),
+        };
+        let (column, name) = match column {
+            Some(column) => {
+                let idx = headers
+                    .iter()
+                    .position(|h| h == column)
+                    .ok_or_else(|| anyhow!("unknown column {column:?} in --agg"))?;
+                (Some(idx), format!("{name}_{column}"))
+            }
+            None if func == AggFunc::Count => (None, "count".to_string()),
+            None => bail!("aggregate {spec:?} needs a column, e.g. {spec}:salary"),
+        };
+        Ok(Self { func, column, name })
+    }
+
+    fn update(&self, state: &mut AggState, record: &StringRecord) -> Result<()> {
+        let Some(column) = self.column else {
+            state.count += 1;
+            return Ok(());
+        };
+        let cell = record.get(column).unwrap_or_default().trim();
+        if cell.is_empty() {
+            return Ok(());
+        }
+        state.count += 1;
+        if self.func != AggFunc::Count {
+            let n: f64 = cell
+                .parse()
+                .map_err(|_| anyhow!("{} expects numbers, got {cell:?}", self.name))?;
+            state.sum += n;
+            state.min = state.min.min(n);
+            state.max = state.max.max(n);
+        }
+        Ok(())
+    }
+
+    fn finish(&self, state: &AggState) -> Value {
+        let empty = state.count == 0;
+        match self.func {
+            AggFunc::Count => Value::from(state.count),
+            AggFunc::Sum => number(state.sum),
+            AggFunc::Avg if empty => Value::Null,
+            AggFunc::Avg => Value::from(state.sum / state.count as f64),
+            AggFunc::Min if empty => Value::Null,
+            AggFunc::Min => number(state.min),
+            AggFunc::Max if empty => Value::Null,
+            AggFunc::Max => number(state.max),
+        }
+    }
+}
+
+impl Default for AggState {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+}
+
+/// 整数值输出为整数，避免 `1500.0` 这样的写法
+fn number(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < (1u64 << 53) as f64 {
+        Value::from(n as i64)
+    } else {
+        Value::from(n)
+    }
+}
+
+pub fn process_csv_groupby(opts: CsvGroupByOpts) -> Result<()> {
+    let (reader, headers) = open_csv(&opts.read)?;
+    let input = &opts.read.input;
+    let output = default_output(input, opts.output.as_deref(), &opts.format.to_string());
+    let groups = groupby_csv(&opts, reader, &headers, get_writer(&output)?)?;
+    eprintln!("{input} -> {output}, {groups} groups, Done.");
+
+    Ok(())
+}
+
+/// 按分组列聚合，分组按首次出现的顺序输出，返回分组数
+pub fn groupby_csv<R: Read>(
+    opts: &CsvGroupByOpts,
+    mut reader: Reader<R>,
+    headers: &StringRecord,
+    output: Box<dyn Write>,
+) -> Result<usize> {
+    let keys = resolve_columns(headers, &opts.by, "groupby")?;
+    let aggs = opts
+        .agg
+        .iter()
+        .map(|spec| Aggregate::parse(spec, headers))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut index: HashMap<Vec<String>, usize> = HashMap::new();
+    let mut groups: Vec<(Vec<String>, Vec<AggState>)> = Vec::new();
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        let key: Vec<String> = keys
+            .iter()
+            .map(|&i| record.get(i).unwrap_or_default().to_string())
+            .collect();
+        let idx = *index.entry(key.clone()).or_insert_with(|| {
+            groups.push((key, vec![AggState::default(); aggs.len()]));
+            groups.len() - 1
+        });
+        for (agg, state) in aggs.iter().zip(groups[idx].1.iter_mut()) {
+            agg.update(state, &record).with_context(|| {
+                let line = record.position().map_or(0, |pos| pos.line());
+                format!("line {line}")
+            })?;
+        }
+    }
+
+    let mut out_headers: StringRecord = opts.by.iter().collect();
+    for agg in &aggs {
+        out_headers.push_field(&agg.name);
+    }
+    let mut writer = record_writer(&opts.format, output)?;
+    writer.write_header(&out_headers)?;
+    for (key, states) in &groups {
+        let mut map = Map::new();
+        for (name, value) in opts.by.iter().zip(key) {
+            map.insert(name.clone(), Value::String(value.clone()));
+        }
+        for (agg, state) in aggs.iter().zip(states) {
+            map.insert(agg.name.clone(), agg.finish(state));
+        }
+        writer.write_record(&Value::Object(map))?;
+    }
+    writer.finish()?;
+
+    Ok(groups.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::{process::csv_reader::csv_reader, utils::SharedBuf};
+
+    const PLAYERS: &str = "name,club,salary,age\nBuffon,Juventus,1500,41\nDybala,Juventus,2000.5,25\nPogba,United,3000,\nKean,Juventus,,19\n";
+
+    fn groupby(args: &[&str]) -> Result<String> {
+        let opts = CsvGroupByOpts::try_parse_from(["groupby", "-i", "-"].iter().chain(args))?;
+        let (reader, headers) = csv_reader(&opts.read, PLAYERS.as_bytes())?;
+        let buf = SharedBuf::default();
+        groupby_csv(&opts, reader, &headers, Box::new(buf.clone()))?;
+        buf.contents()
+    }
+
+    #[test]
+    fn test_groupby_aggregates() -> Result<()> {
+        assert_eq!(
+            groupby(&["club", "--agg", "count,sum:salary,avg:age,max:age"])?,
+            "club,count,sum_salary,avg_age,max_age\n\
+             Juventus,3,3500.5,28.333333333333332,41\n\
+             United,1,3000,,\n"
+        );
+        assert_eq!(groupby(&["club"])?, "club,count\nJuventus,3\nUnited,1\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_groupby_errors() {
+        assert!(groupby(&["club", "--agg", "sum"]).is_err());
+        assert!(groupby(&["club", "--agg", "median:age"]).is_err());
+        assert!(groupby(&["club", "--agg", "sum:name"]).is_err());
+        assert!(groupby(&["league"]).is_err());
+    }
+}
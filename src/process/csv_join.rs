@@ -0,0 +1,344 @@
+use std::{collections::HashMap, fs, io::Read, io::Write};
+
+use anyhow::{anyhow, bail, Result};
+use csv::{Reader, StringRecord};
+
+use crate::{
+    cli::csv::{CsvJoinOpts, JoinKind},
+    utils::{default_output, get_writer},
+};
+
+use super::{
+    csv_reader::{dialect_reader, open_input},
+    csv_types::Schema,
+    csv_writer::record_writer,
+};
+
+/// 哈希表建在哪一侧，另一侧流式读取
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+pub fn process_csv_join(opts: CsvJoinOpts) -> Result<()> {
+    if opts.left == "-" && opts.right == "-" {
+        bail!("only one side of the join can read from stdin");
+    }
+    // stdin 无法预知大小，总是作为流式读取的一侧
+    let size = |path: &str| match path {
+        "-" => u64::MAX,
+        path => fs::metadata(path).map_or(u64::MAX, |m| m.len()),
+    };
+    let build = if size(&opts.left) <= size(&opts.right) {
+        Side::Left
+    } else {
+        Side::Right
+    };
+
+    let output = default_output(&opts.left, opts.output.as_deref(), &opts.format.to_string());
+    let rows = join_csv(
+        &opts,
+        open_input(&opts.left)?,
+        open_input(&opts.right)?,
+        build,
+        get_writer(&output)?,
+    )?;
+    eprintln!(
+        "{} {} join {} -> {output}, {rows} rows, Done.",
+        opts.left, opts.how, opts.right
+    );
+
+    Ok(())
+}
+
+/// 哈希关联：将 `build` 一侧读入内存，逐行读取另一侧匹配，返回输出行数
+///
+/// 无论哪一侧建哈希表，输出都按左侧顺序排列，同一左侧行的多个匹配按右侧顺序排列，
+/// right / outer join 中未匹配的右侧行追加在最后。
+/// 输出列为左侧全部列加右侧除关联列外的列，重名列加 `_right` 后缀。
+/// 关联列为空的行不参与匹配。
+pub fn join_csv(
+    opts: &CsvJoinOpts,
+    left: impl Read,
+    right: impl Read,
+    build: Side,
+    output: Box<dyn Write>,
+) -> Result<usize> {
+    let (mut left_reader, left_headers) = dialect_reader(&opts.dialect, &[], left)?;
+    let (mut right_reader, right_headers) = dialect_reader(&opts.dialect, &[], right)?;
+    let (left_on, right_on) = opts.on.split_once('=').unwrap_or((&opts.on, &opts.on));
+    let layout = JoinLayout::new(
+        &left_headers,
+        &right_headers,
+        column_index(&left_headers, left_on, "left")?,
+        column_index(&right_headers, right_on, "right")?,
+    );
+
+    let schema = Schema::new(Default::default(), opts.infer_types);
+    let mut writer = record_writer(&opts.format, output)?;
+    writer.write_header(&layout.headers)?;
+
+    let keep_left = matches!(opts.how, JoinKind::Left | JoinKind::Outer);
+    let keep_right = matches!(opts.how, JoinKind::Right | JoinKind::Outer);
+    let mut rows = 0;
+    let mut merged = StringRecord::new();
+    let mut emit = |left: Option<&StringRecord>, right: Option<&StringRecord>| {
+        layout.merge(left, right, &mut merged);
+        rows += 1;
+        writer.write_record(&schema.record_to_value(&layout.headers, &merged)?)
+    };
+
+    match build {
+        Side::Right => {
+            let (table, index) = load_table(&mut right_reader, layout.right_key)?;
+            let mut matched = vec![false; table.len()];
+            let mut record = StringRecord::new();
+            while left_reader.read_record(&mut record)? {
+                match lookup(&index, &record, layout.left_key) {
+                    Some(hits) => {
+                        for &i in hits {
+                            matched[i] = true;
+                            emit(Some(&record), Some(&table[i]))?;
+                        }
+                    }
+                    None if keep_left => emit(Some(&record), None)?,
+                    None => {}
+                }
+            }
+            if keep_right {
+                for (row, _) in table.iter().zip(&matched).filter(|(_, m)| !**m) {
+                    emit(None, Some(row))?;
+                }
+            }
+        }
+        Side::Left => {
+            // 右侧匹配的行按左侧行号收集，读完右侧后按左侧顺序输出
+            let (table, index) = load_table(&mut left_reader, layout.left_key)?;
+            let mut hits: Vec<Vec<usize>> = vec![Vec::new(); table.len()];
+            let mut matches: Vec<StringRecord> = Vec::new();
+            let mut unmatched: Vec<StringRecord> = Vec::new();
+            for record in right_reader.records() {
+                let record = record?;
+                match lookup(&index, &record, layout.right_key) {
+                    Some(rows) => {
+                        for &i in rows {
+                            hits[i].push(matches.len());
+                        }
+                        matches.push(record);
+                    }
+                    None if keep_right => unmatched.push(record),
+                    None => {}
+                }
+            }
+            for (row, hits) in table.iter().zip(&hits) {
+                if hits.is_empty() && keep_left {
+                    emit(Some(row), None)?;
+                }
+                for &j in hits {
+                    emit(Some(row), Some(&matches[j]))?;
+                }
+            }
+            for row in &unmatched {
+                emit(None, Some(row))?;
+            }
+        }
+    }
+    writer.finish()?;
+
+    Ok(rows)
+}
+
+/// 关联列的值到行号的索引
+type KeyIndex = HashMap<String, Vec<usize>>;
+
+/// 将一侧读入内存，按关联列建立索引，关联列为空的行不进入索引
+fn load_table<R: Read>(
+    reader: &mut Reader<R>,
+    key: usize,
+) -> Result<(Vec<StringRecord>, KeyIndex)> {
+    let mut table = Vec::new();
+    let mut index = KeyIndex::new();
+    for record in reader.records() {
+        let record = record?;
+        let value = record.get(key).unwrap_or_default();
+        if !value.is_empty() {
+            index
+                .entry(value.to_string())
+                .or_default()
+                .push(table.len());
+        }
+        table.push(record);
+    }
+    Ok((table, index))
+}
+
+fn lookup<'a>(index: &'a KeyIndex, record: &StringRecord, key: usize) -> Option<&'a Vec<usize>> {
+    let value = record.get(key).unwrap_or_default();
+    index.get(value).filter(|_| !value.is_empty())
+}
+
+fn column_index(headers: &StringRecord, name: &str, side: &str) -> Result<usize> {
+    headers
+        .iter()
+        .position(|h| h == name)
+        .ok_or_else(|| anyhow!("unknown column {name:?} in {side} input"))
+}
+
+/// 关联结果的列布局
+#[derive(Debug)]
+struct JoinLayout {
+    headers: StringRecord,
+    left_len: usize,
+    left_key: usize,
+    right_key: usize,
+}
+
+impl JoinLayout {
+    fn new(left: &StringRecord, right: &StringRecord, left_key: usize, right_key: usize) -> Self {
+        let mut headers = left.clone();
+        for (i, name) in right.iter().enumerate() {
+            if i == right_key {
+                continue;
+            }
+            if left.iter().any(|h| h == name) {
+                headers.push_field(&format!("{name}_right"));
+            } else {
+                headers.push_field(name);
+            }
+        }
+        Self {
+            headers,
+            left_len: left.len(),
+            left_key,
+            right_key,
+        }
+    }
+
+    /// 合并两侧的行，缺失一侧的列留空，关联列取存在的一侧
+    fn merge(
+        &self,
+        left: Option<&StringRecord>,
+        right: Option<&StringRecord>,
+        out: &mut StringRecord,
+    ) {
+        fn cell(row: Option<&StringRecord>, i: usize) -> &str {
+            row.and_then(|row| row.get(i)).unwrap_or_default()
+        }
+        out.clear();
+        for i in 0..self.left_len {
+            if i == self.left_key && left.is_none() {
+                out.push_field(cell(right, self.right_key));
+            } else {
+                out.push_field(cell(left, i));
+            }
+        }
+        let right_len = self.headers.len() - self.left_len + 1;
+        for i in (0..right_len).filter(|&i| i != self.right_key) {
+            out.push_field(cell(right, i));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::utils::SharedBuf;
+
+    const PLAYERS: &str = "id,name,club\n1,Buffon,1\n2,Dybala,1\n3,Pogba,2\n4,Zidane,\n";
+    const CLUBS: &str = "club,name\n1,Juventus\n2,Manchester United\n9,Milan\n";
+
+    /// 两侧分别建哈希表，输出必须一致
+    fn join(how: &str, left: &str, right: &str) -> Result<String> {
+        let opts = CsvJoinOpts::try_parse_from(["join", "-", "-", "--on", "club", "--how", how])?;
+        let mut outputs = Vec::new();
+        for build in [Side::Left, Side::Right] {
+            let buf = SharedBuf::default();
+            join_csv(
+                &opts,
+                left.as_bytes(),
+                right.as_bytes(),
+                build,
+                Box::new(buf.clone()),
+            )?;
+            outputs.push(buf.contents()?);
+        }
+        assert_eq!(outputs[0], outputs[1], "{how}");
+        Ok(outputs.remove(0))
+    }
+
+    #[test]
+    fn test_join_kinds() -> Result<()> {
+        assert_eq!(
+            join("inner", PLAYERS, CLUBS)?,
+            "id,name,club,name_right\n\
+             1,Buffon,1,Juventus\n\
+             2,Dybala,1,Juventus\n\
+             3,Pogba,2,Manchester United\n"
+        );
+        assert_eq!(
+            join("left", PLAYERS, CLUBS)?,
+            "id,name,club,name_right\n\
+             1,Buffon,1,Juventus\n\
+             2,Dybala,1,Juventus\n\
+             3,Pogba,2,Manchester United\n\
+             4,Zidane,,\n"
+        );
+        assert_eq!(
+            join("right", PLAYERS, CLUBS)?,
+            "id,name,club,name_right\n\
+             1,Buffon,1,Juventus\n\
+             2,Dybala,1,Juventus\n\
+             3,Pogba,2,Manchester United\n\
+             ,,9,Milan\n"
+        );
+        assert_eq!(
+            join("outer", PLAYERS, CLUBS)?,
+            "id,name,club,name_right\n\
+             1,Buffon,1,Juventus\n\
+             2,Dybala,1,Juventus\n\
+             3,Pogba,2,Manchester United\n\
+             4,Zidane,,\n\
+             ,,9,Milan\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_keeps_left_order() -> Result<()> {
+        // 右侧顺序不同，输出仍按左侧顺序
+        let left = "club,player\n2,Pogba\n5,Kean\n1,Buffon\n2,Lukaku\n";
+        let right = "club,name\n1,Juventus\n2,Manchester United\n";
+        for how in ["inner", "left", "right", "outer"] {
+            let out = join(how, left, right)?;
+            let players: Vec<&str> = out
+                .lines()
+                .skip(1)
+                .filter_map(|line| line.split(',').nth(1))
+                .collect();
+            let expected = match how {
+                "inner" | "right" => vec!["Pogba", "Buffon", "Lukaku"],
+                _ => vec!["Pogba", "Kean", "Buffon", "Lukaku"],
+            };
+            assert_eq!(players, expected, "{how}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_layout_different_keys() {
+        let left = StringRecord::from(vec!["id", "name"]);
+        let right = StringRecord::from(vec!["player_id", "goals"]);
+        let layout = JoinLayout::new(&left, &right, 0, 0);
+        assert_eq!(
+            layout.headers,
+            StringRecord::from(vec!["id", "name", "goals"])
+        );
+
+        let mut out = StringRecord::new();
+        layout.merge(None, Some(&StringRecord::from(vec!["7", "12"])), &mut out);
+        assert_eq!(out, StringRecord::from(vec!["7", "", "12"]));
+    }
+}
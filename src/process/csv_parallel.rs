@@ -0,0 +1,292 @@
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom, Write},
+    thread,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use csv::StringRecord;
+
+use crate::{cli::csv::CsvOpts, utils::SharedBuf};
+
+use super::{
+    csv_convert::{output_writer, Converter},
+    csv_reader::{ascii_byte, read_headers, reader_builder},
+    csv_writer::fragment_writer,
+    spreadsheet::is_spreadsheet,
+};
+
+/// 每个分块的输入字节数
+pub const CHUNK_SIZE: u64 = 8 * 1024 * 1024;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// 可以并行转换时返回线程数
+///
+/// 要求输入为足够大的普通 csv 文件，且方言允许仅凭引号奇偶判断记录边界：
+/// 不转码、无转义字符与注释行、引号以双写转义；列式格式需要整体推断类型，也不并行。
+pub fn parallel_jobs(opts: &CsvOpts) -> Option<usize> {
+    let read = &opts.read;
+    let dialect = &read.dialect;
+    let jobs = match opts.jobs {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    let eligible = jobs > 1
+        && read.input != "-"
+        && !is_spreadsheet(&read.input)
+        && dialect.encoding.is_none()
+        && dialect.escape.is_none()
+        && dialect.comment.is_none()
+        && dialect.double_quote
+        && !opts.format.is_columnar()
+        && fs::metadata(&read.input).is_ok_and(|m| m.is_file() && m.len() >= 2 * CHUNK_SIZE)
+        && !has_utf16_bom(&read.input);
+    eligible.then_some(jobs)
+}
+
+fn has_utf16_bom(input: &str) -> bool {
+    let mut prefix = [0; 2];
+    File::open(input)
+        .and_then(|mut file| file.read_exact(&mut prefix))
+        .is_ok_and(|_| prefix == [0xFF, 0xFE] || prefix == [0xFE, 0xFF])
+}
+
+/// 分块扫描结果：引号个数，以及块内第一个位于偶数 / 奇数个引号之后的换行符位置
+#[derive(Debug, Default)]
+struct Scan {
+    quotes: u64,
+    first_even: Option<u64>,
+    first_odd: Option<u64>,
+}
+
+/// 按字节范围切分后并行转换，输出与 `convert_csv` 一致，返回处理的行数
+///
+/// 先并行统计每块的引号个数，由此前缀的奇偶性确定各块中第一个不在引号内的换行符，
+/// 以其后一个字节作为分块边界；再每批 `jobs` 块并行转换，按顺序写出。
+pub fn convert_csv_parallel(
+    opts: &CsvOpts,
+    jobs: usize,
+    chunk_size: u64,
+    output: Box<dyn Write>,
+) -> Result<usize> {
+    let input = &opts.read.input;
+    let dialect = &opts.read.dialect;
+    let mut file = File::open(input)?;
+    let len = file.metadata()?.len();
+
+    let mut prefix = Vec::new();
+    (&mut file).take(3).read_to_end(&mut prefix)?;
+    let base = if prefix == UTF8_BOM { 3 } else { 0 };
+    file.seek(SeekFrom::Start(base))?;
+    let mut reader = reader_builder(dialect)?.from_reader(file);
+    let headers = read_headers(&mut reader, dialect.header, &opts.read.columns)?;
+    let start = if dialect.header {
+        base + reader.position().byte()
+    } else {
+        base
+    };
+
+    let quote = ascii_byte(dialect.quote, "quote")?;
+    let ranges: Vec<(u64, u64)> = (start..len)
+        .step_by(chunk_size.max(1) as usize)
+        .map(|s| (s, (s + chunk_size).min(len)))
+        .collect();
+    let mut scans = Vec::with_capacity(ranges.len());
+    run_batched(
+        jobs,
+        &ranges,
+        |&range| scan(input, range, quote),
+        |s| {
+            scans.push(s);
+            Ok(())
+        },
+    )?;
+
+    let mut bounds = vec![start];
+    let mut odd = false;
+    for (i, scan) in scans.iter().enumerate() {
+        let newline = if odd { scan.first_odd } else { scan.first_even };
+        if let Some(pos) = newline.filter(|_| i > 0) {
+            bounds.push(pos + 1);
+        }
+        odd ^= scan.quotes % 2 == 1;
+    }
+    bounds.push(len);
+    let chunks: Vec<(u64, u64)> = bounds.windows(2).map(|w| (w[0], w[1])).collect();
+
+    let converter = Converter::new(opts, &headers)?;
+    let mut writer = output_writer(opts, output)?;
+    writer.write_header(converter.headers())?;
+    let mut rows = 0;
+    run_batched(
+        jobs,
+        &chunks,
+        |&chunk| convert_chunk(opts, &converter, &headers, chunk),
+        |(fragment, n)| {
+            writer.write_fragment(&fragment, n)?;
+            rows += n;
+            Ok(())
+        },
+    )?;
+    writer.finish()?;
+
+    Ok(rows)
+}
+
+/// 每批最多 `jobs` 个任务并行执行，结果按输入顺序交给 `each`
+fn run_batched<T: Sync, R: Send>(
+    jobs: usize,
+    items: &[T],
+    task: impl Fn(&T) -> Result<R> + Sync,
+    mut each: impl FnMut(R) -> Result<()>,
+) -> Result<()> {
+    let task = &task;
+    for batch in items.chunks(jobs.max(1)) {
+        let results = thread::scope(|scope| {
+            let handles: Vec<_> = batch
+                .iter()
+                .map(|item| scope.spawn(move || task(item)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| {
+                    h.join()
+                        .map_err(|_| anyhow!("conversion thread panicked"))?
+                })
+                .collect::<Result<Vec<_>>>()
+        })?;
+        for result in results {
+            each(result)?;
+        }
+    }
+    Ok(())
+}
+
+fn scan(input: &str, (start, end): (u64, u64), quote: u8) -> Result<Scan> {
+    let mut file = File::open(input)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut buf = Vec::with_capacity((end - start) as usize);
+    file.take(end - start).read_to_end(&mut buf)?;
+
+    let mut result = Scan::default();
+    for (i, &b) in buf.iter().enumerate() {
+        if b == quote {
+            result.quotes += 1;
+        } else if b == b'\n' {
+            let slot = if result.quotes % 2 == 0 {
+                &mut result.first_even
+            } else {
+                &mut result.first_odd
+            };
+            slot.get_or_insert(start + i as u64);
+        }
+    }
+    Ok(result)
+}
+
+/// 转换一个分块，返回渲染好的输出片段与行数
+fn convert_chunk(
+    opts: &CsvOpts,
+    converter: &Converter,
+    headers: &StringRecord,
+    (start, end): (u64, u64),
+) -> Result<(Vec<u8>, usize)> {
+    let mut file = File::open(&opts.read.input)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut reader = reader_builder(&opts.read.dialect)?
+        .has_headers(false)
+        .from_reader(file.take(end - start));
+
+    let buf = SharedBuf::default();
+    let mut writer = fragment_writer(&opts.format, Box::new(buf.clone()), converter.headers())?;
+    let mut record = StringRecord::new();
+    let mut projected = StringRecord::new();
+    let mut rows = 0;
+    // 错误信息中的位置为整个文件内的字节偏移
+    let offset = |record: &StringRecord| start + record.position().map_or(0, |pos| pos.byte());
+    while reader
+        .read_record(&mut record)
+        .with_context(|| format!("chunk starting at byte {start}"))?
+    {
+        if !opts.read.dialect.flexible && record.len() != headers.len() {
+            bail!(
+                "byte {}: found record with {} fields, but the header has {}",
+                offset(&record),
+                record.len(),
+                headers.len()
+            );
+        }
+        let converted = converter
+            .convert(&record, &mut projected)
+            .with_context(|| format!("byte {}", offset(&record)))?;
+        if let Some(value) = converted {
+            writer.write_record(&value)?;
+            rows += 1;
+        }
+    }
+    writer.flush()?;
+    drop(writer);
+
+    Ok((buf.take(), rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::process::csv_convert::convert_csv;
+
+    const PLAYERS: &str = "\u{feff}name,bio,kit\r\n\
+                           Buffon,\"Goalkeeper\nsince \"\"2001\"\"\",77\r\n\
+                           Dybala,\"La Joya, \"\"10\"\"\",10\r\n\
+                           \r\n\
+                           Kean,\"multi\nline\nbio\",18\r\n\
+                           Pjanic,,5\r\n";
+
+    fn compare(args: &[&str], content: &str) -> Result<()> {
+        let mut file = tempfile::Builder::new().suffix(".csv").tempfile()?;
+        file.write_all(content.as_bytes())?;
+        let path = file.path().to_string_lossy().to_string();
+        let opts = CsvOpts::try_parse_from(["csv", "-i", &path].iter().chain(args))?;
+
+        let expected = SharedBuf::default();
+        let rows = convert_csv(&opts, File::open(&path)?, Box::new(expected.clone()))?;
+        // 分块大小从 1 字节到整个文件，覆盖边界落在引号内、换行符上等各种情况
+        for chunk_size in 1..=content.len() as u64 {
+            let actual = SharedBuf::default();
+            let n = convert_csv_parallel(&opts, 3, chunk_size, Box::new(actual.clone()))?;
+            assert_eq!(
+                (actual.contents()?, n),
+                (expected.contents()?, rows),
+                "{args:?}, chunk size {chunk_size}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() -> Result<()> {
+        for format in ["json", "yaml", "toml", "ndjson", "csv", "tsv"] {
+            compare(&["-f", format, "--infer-types"], PLAYERS)?;
+        }
+        compare(&["-f", "json", "--where", "kit > 50"], PLAYERS)?;
+        compare(&["-f", "json", "--where", "kit > 100"], PLAYERS)?;
+        compare(
+            &["-f", "csv", "--header", "false"],
+            "a,1\nb,\"x\ny\"\nc,3\n",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_field_count_mismatch() -> Result<()> {
+        let mut file = tempfile::Builder::new().suffix(".csv").tempfile()?;
+        file.write_all(b"a,b\n1,2\n3\n")?;
+        let path = file.path().to_string_lossy().to_string();
+        let opts = CsvOpts::try_parse_from(["csv", "-i", &path])?;
+        assert!(convert_csv_parallel(&opts, 2, 4, Box::new(SharedBuf::default())).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,1033 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use csv::StringRecord;
+use regex::{Regex, RegexBuilder};
+use serde_json::{Map, Value};
+use sqlparser::{
+    ast::{self, BinaryOperator, FunctionArg, FunctionArgExpr, FunctionArguments, UnaryOperator},
+    dialect::GenericDialect,
+    parser::Parser,
+};
+
+use crate::{cli::csv::CsvQueryOpts, utils::get_writer};
+
+use super::{
+    csv_reader::{check_unique_headers, dialect_reader, open_input},
+    csv_types::infer_value,
+    csv_writer::record_writer,
+    table::{use_color, Table},
+};
+
+/// 编译后的表达式，列名已解析为下标，聚合函数替换为 `Agg(i)`
+#[derive(Debug)]
+enum Expr {
+    Column(usize),
+    Literal(Value),
+    Agg(usize),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, BinOp, Box<Expr>),
+    IsNull(Box<Expr>, bool),
+    InList(Box<Expr>, Vec<Expr>, bool),
+    Between(Box<Expr>, Box<Expr>, Box<Expr>, bool),
+    Like(Box<Expr>, Regex, bool),
+    Call(Scalar, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Concat,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    Lower,
+    Upper,
+    Length,
+    Trim,
+    Abs,
+    Round,
+    Coalesce,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AggFunc {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+#[derive(Debug)]
+struct AggCall {
+    func: AggFunc,
+    /// `count(*)` 时为 `None`
+    arg: Option<Expr>,
+    distinct: bool,
+}
+
+#[derive(Debug, Default)]
+struct AggState {
+    count: i64,
+    int_sum: i64,
+    float_sum: f64,
+    float: bool,
+    min: Option<Value>,
+    max: Option<Value>,
+    seen: HashSet<String>,
+}
+
+#[derive(Debug)]
+enum OrderKey {
+    /// 按输出列排序：别名或 `ORDER BY 2`
+    Output(usize),
+    Expr(Expr),
+}
+
+/// 单表查询计划
+#[derive(Debug)]
+pub struct Plan {
+    names: Vec<String>,
+    select: Vec<Expr>,
+    filter: Option<Expr>,
+    group_by: Vec<Expr>,
+    having: Option<Expr>,
+    aggs: Vec<AggCall>,
+    order_by: Vec<(OrderKey, bool)>,
+    distinct: bool,
+    limit: Option<usize>,
+    offset: usize,
+}
+
+/// 解析 SQL，返回表名与尚未绑定列的查询
+pub fn parse_query(sql: &str) -> Result<(String, ast::Query)> {
+    let mut statements = Parser::parse_sql(&GenericDialect {}, sql)?;
+    if statements.len() != 1 {
+        bail!("expected exactly one SQL statement");
+    }
+    let ast::Statement::Query(query) = statements.remove(0) else {
+        bail!("only SELECT queries are supported");
+    };
+    let ast::SetExpr::Select(select) = query.body.as_ref() else {
+        bail!("only simple SELECT queries are supported");
+    };
+    let table = match select.from.as_slice() {
+        [from] if from.joins.is_empty() => match &from.relation {
+            ast::TableFactor::Table { name, .. } => name
+                .0
+                .last()
+                .map(|ident| ident.value.clone())
+                .ok_or_else(|| anyhow!("missing table name"))?,
+            relation => bail!("unsupported table expression {relation}"),
+        },
+        [_] => bail!("JOIN is not supported, use `rcli csv join` first"),
+        [] => bail!("missing FROM clause"),
+        _ => bail!("querying multiple tables is not supported"),
+    };
+    Ok((table, *query))
+}
+
+impl Plan {
+    pub fn new(query: &ast::Query, headers: &StringRecord) -> Result<Self> {
+        let ast::SetExpr::Select(select) = query.body.as_ref() else {
+            bail!("only simple SELECT queries are supported");
+        };
+        let mut compiler = Compiler {
+            headers,
+            aggs: Vec::new(),
+            allow_aggs: false,
+        };
+
+        let filter = select
+            .selection
+            .as_ref()
+            .map(|e| compiler.expr(e))
+            .transpose()
+            .context("in WHERE")?;
+        let group_by = match &select.group_by {
+            ast::GroupByExpr::Expressions(exprs, _) => exprs
+                .iter()
+                .map(|e| compiler.expr(e))
+                .collect::<Result<Vec<_>>>()
+                .context("in GROUP BY")?,
+            ast::GroupByExpr::All(_) => bail!("GROUP BY ALL is not supported"),
+        };
+
+        compiler.allow_aggs = true;
+        let mut names = Vec::new();
+        let mut exprs = Vec::new();
+        for item in &select.projection {
+            match item {
+                ast::SelectItem::UnnamedExpr(e) => {
+                    names.push(match e {
+                        ast::Expr::Identifier(ident) => ident.value.clone(),
+                        ast::Expr::CompoundIdentifier(idents) => {
+                            idents.last().map(|i| i.value.clone()).unwrap_or_default()
+                        }
+                        e => e.to_string(),
+                    });
+                    exprs.push(compiler.expr(e)?);
+                }
+                ast::SelectItem::ExprWithAlias { expr, alias } => {
+                    names.push(alias.value.clone());
+                    exprs.push(compiler.expr(expr)?);
+                }
+                ast::SelectItem::Wildcard(_) | ast::SelectItem::QualifiedWildcard(..) => {
+                    names.extend(headers.iter().map(String::from));
+                    exprs.extend((0..headers.len()).map(Expr::Column));
+                }
+            }
+        }
+        let having = select
+            .having
+            .as_ref()
+            .map(|e| compiler.expr(e))
+            .transpose()
+            .context("in HAVING")?;
+
+        let mut order_by = Vec::new();
+        for item in query.order_by.iter().flat_map(|o| &o.exprs) {
+            let key = match &item.expr {
+                ast::Expr::Identifier(ident) if names.contains(&ident.value) => {
+                    OrderKey::Output(names.iter().position(|n| *n == ident.value).unwrap_or(0))
+                }
+                ast::Expr::Value(ast::Value::Number(n, _)) => match n.parse::<usize>() {
+                    Ok(i) if (1..=names.len()).contains(&i) => OrderKey::Output(i - 1),
+                    _ => bail!("ORDER BY position {n} is out of range"),
+                },
+                e => OrderKey::Expr(compiler.expr(e).context("in ORDER BY")?),
+            };
+            order_by.push((key, item.asc == Some(false)));
+        }
+
+        // 聚合查询中未分组也未聚合的列在每组中取值不确定，编译时拒绝
+        if !group_by.is_empty() || !compiler.aggs.is_empty() {
+            let grouped: Vec<String> = group_by.iter().map(|e| format!("{e:?}")).collect();
+            let check = |expr: &Expr, clause: &str| {
+                check_grouped(expr, &grouped, headers).with_context(|| format!("in {clause}"))
+            };
+            for expr in &exprs {
+                check(expr, "SELECT")?;
+            }
+            if let Some(expr) = &having {
+                check(expr, "HAVING")?;
+            }
+            for (key, _) in &order_by {
+                if let OrderKey::Expr(expr) = key {
+                    check(expr, "ORDER BY")?;
+                }
+            }
+        }
+
+        Ok(Self {
+            names,
+            select: exprs,
+            filter,
+            group_by,
+            having,
+            aggs: compiler.aggs,
+            order_by,
+            distinct: matches!(select.distinct, Some(ast::Distinct::Distinct)),
+            limit: query.limit.as_ref().map(count).transpose()?,
+            offset: query
+                .offset
+                .as_ref()
+                .map(|o| count(&o.value))
+                .transpose()?
+                .unwrap_or(0),
+        })
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// 执行查询；无排序、去重与聚合时读到 LIMIT 行即停止
+    pub fn execute(
+        &self,
+        rows: impl Iterator<Item = Result<Vec<Value>>>,
+    ) -> Result<Vec<Vec<Value>>> {
+        let aggregate = !self.group_by.is_empty() || !self.aggs.is_empty();
+        let streaming = !aggregate && !self.distinct && self.order_by.is_empty();
+        let wanted = self.limit.map(|limit| self.offset + limit);
+
+        let mut results = Vec::new();
+        let mut groups: Vec<(Vec<Value>, Vec<AggState>)> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        for row in rows {
+            let row = row?;
+            if let Some(filter) = &self.filter {
+                if !truthy(&filter.eval(&row, &[])?) {
+                    continue;
+                }
+            }
+            if !aggregate {
+                results.push(self.project(&row, &[])?);
+                if streaming && wanted.is_some_and(|n| results.len() >= n) {
+                    break;
+                }
+                continue;
+            }
+
+            let key = self
+                .group_by
+                .iter()
+                .map(|e| e.eval(&row, &[]))
+                .collect::<Result<Vec<_>>>()?;
+            let key = serde_json::to_string(&key)?;
+            let idx = *index.entry(key).or_insert_with(|| {
+                let states = self.aggs.iter().map(|_| AggState::default()).collect();
+                groups.push((row.clone(), states));
+                groups.len() - 1
+            });
+            for (call, state) in self.aggs.iter().zip(groups[idx].1.iter_mut()) {
+                state.update(call, &row)?;
+            }
+        }
+
+        if aggregate {
+            // 没有 GROUP BY 时即使没有任何行也输出一行聚合结果
+            if groups.is_empty() && self.group_by.is_empty() {
+                let states = self.aggs.iter().map(|_| AggState::default()).collect();
+                groups.push((Vec::new(), states));
+            }
+            for (row, states) in &groups {
+                let values: Vec<Value> = self
+                    .aggs
+                    .iter()
+                    .zip(states)
+                    .map(|(call, state)| state.finish(call.func))
+                    .collect();
+                if let Some(having) = &self.having {
+                    if !truthy(&having.eval(row, &values)?) {
+                        continue;
+                    }
+                }
+                results.push(self.project(row, &values)?);
+            }
+        }
+
+        if self.distinct {
+            let mut seen = HashSet::new();
+            results.retain(|(out, _)| seen.insert(serde_json::to_string(out).unwrap_or_default()));
+        }
+        if !self.order_by.is_empty() {
+            results.sort_by(|(_, a), (_, b)| {
+                a.iter()
+                    .zip(b)
+                    .zip(&self.order_by)
+                    .map(|((a, b), (_, desc))| {
+                        let ordering = sort_order(a, b);
+                        if *desc {
+                            ordering.reverse()
+                        } else {
+                            ordering
+                        }
+                    })
+                    .find(|o| *o != Ordering::Equal)
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+
+        Ok(results
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .map(|(out, _)| out)
+            .collect())
+    }
+
+    /// 计算输出列与排序键
+    fn project(&self, row: &[Value], aggs: &[Value]) -> Result<(Vec<Value>, Vec<Value>)> {
+        let out = self
+            .select
+            .iter()
+            .map(|e| e.eval(row, aggs))
+            .collect::<Result<Vec<_>>>()?;
+        let keys = self
+            .order_by
+            .iter()
+            .map(|(key, _)| match key {
+                OrderKey::Output(i) => Ok(out[*i].clone()),
+                OrderKey::Expr(e) => e.eval(row, aggs),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok((out, keys))
+    }
+}
+
+/// 检查表达式只引用 GROUP BY 中的表达式或聚合结果，`grouped` 为分组表达式的调试输出
+fn check_grouped(expr: &Expr, grouped: &[String], headers: &StringRecord) -> Result<()> {
+    if grouped.contains(&format!("{expr:?}")) {
+        return Ok(());
+    }
+    let check = |e: &Expr| check_grouped(e, grouped, headers);
+    match expr {
+        Expr::Column(i) => bail!(
+            "column {:?} must appear in GROUP BY or be used in an aggregate function",
+            headers.get(*i).unwrap_or_default()
+        ),
+        Expr::Literal(_) | Expr::Agg(_) => Ok(()),
+        Expr::Not(e) | Expr::Neg(e) | Expr::IsNull(e, _) | Expr::Like(e, ..) => check(e),
+        Expr::Binary(lhs, _, rhs) => check(lhs).and_then(|_| check(rhs)),
+        Expr::InList(e, list, _) => std::iter::once(e.as_ref()).chain(list).try_for_each(check),
+        Expr::Between(e, low, high, _) => [e, low, high].into_iter().try_for_each(|e| check(e)),
+        Expr::Call(_, args) => args.iter().try_for_each(check),
+    }
+}
+
+fn count(expr: &ast::Expr) -> Result<usize> {
+    match expr {
+        ast::Expr::Value(ast::Value::Number(n, _)) => Ok(n.parse()?),
+        e => bail!("expected a number, got {e}"),
+    }
+}
+
+struct Compiler<'a> {
+    headers: &'a StringRecord,
+    aggs: Vec<AggCall>,
+    allow_aggs: bool,
+}
+
+impl Compiler<'_> {
+    fn expr(&mut self, expr: &ast::Expr) -> Result<Expr> {
+        Ok(match expr {
+            ast::Expr::Identifier(ident) => Expr::Column(self.column(ident)?),
+            ast::Expr::CompoundIdentifier(idents) => match idents.last() {
+                Some(ident) => Expr::Column(self.column(ident)?),
+                None => bail!("empty identifier"),
+            },
+            ast::Expr::Value(value) => Expr::Literal(literal(value)?),
+            ast::Expr::Nested(e) => self.expr(e)?,
+            ast::Expr::UnaryOp { op, expr } => match op {
+                UnaryOperator::Not => Expr::Not(Box::new(self.expr(expr)?)),
+                UnaryOperator::Minus => Expr::Neg(Box::new(self.expr(expr)?)),
+                UnaryOperator::Plus => self.expr(expr)?,
+                op => bail!("unsupported operator {op}"),
+            },
+            ast::Expr::BinaryOp { left, op, right } => {
+                let op = match op {
+                    BinaryOperator::Plus => BinOp::Add,
+                    BinaryOperator::Minus => BinOp::Sub,
+                    BinaryOperator::Multiply => BinOp::Mul,
+                    BinaryOperator::Divide => BinOp::Div,
+                    BinaryOperator::Modulo => BinOp::Mod,
+                    BinaryOperator::StringConcat => BinOp::Concat,
+                    BinaryOperator::Eq => BinOp::Eq,
+                    BinaryOperator::NotEq => BinOp::Ne,
+                    BinaryOperator::Lt => BinOp::Lt,
+                    BinaryOperator::LtEq => BinOp::Le,
+                    BinaryOperator::Gt => BinOp::Gt,
+                    BinaryOperator::GtEq => BinOp::Ge,
+                    BinaryOperator::And => BinOp::And,
+                    BinaryOperator::Or => BinOp::Or,
+                    op => bail!("unsupported operator {op}"),
+                };
+                Expr::Binary(Box::new(self.expr(left)?), op, Box::new(self.expr(right)?))
+            }
+            ast::Expr::IsNull(e) => Expr::IsNull(Box::new(self.expr(e)?), false),
+            ast::Expr::IsNotNull(e) => Expr::IsNull(Box::new(self.expr(e)?), true),
+            ast::Expr::InList {
+                expr,
+                list,
+                negated,
+            } => Expr::InList(
+                Box::new(self.expr(expr)?),
+                list.iter().map(|e| self.expr(e)).collect::<Result<_>>()?,
+                *negated,
+            ),
+            ast::Expr::Between {
+                expr,
+                negated,
+                low,
+                high,
+            } => Expr::Between(
+                Box::new(self.expr(expr)?),
+                Box::new(self.expr(low)?),
+                Box::new(self.expr(high)?),
+                *negated,
+            ),
+            ast::Expr::Like {
+                negated,
+                expr,
+                pattern,
+                ..
+            } => Expr::Like(Box::new(self.expr(expr)?), like(pattern, false)?, *negated),
+            ast::Expr::ILike {
+                negated,
+                expr,
+                pattern,
+                ..
+            } => Expr::Like(Box::new(self.expr(expr)?), like(pattern, true)?, *negated),
+            ast::Expr::Function(function) => self.function(function)?,
+            e => bail!("unsupported expression {e}"),
+        })
+    }
+
+    fn column(&self, ident: &ast::Ident) -> Result<usize> {
+        let exact = self.headers.iter().position(|h| h == ident.value);
+        // 未加引号的列名不区分大小写
+        let folded = || {
+            let mut matches = self
+                .headers
+                .iter()
+                .enumerate()
+                .filter(|(_, h)| h.eq_ignore_ascii_case(&ident.value));
+            match (matches.next(), matches.next()) {
+                (Some((i, _)), None) => Some(i),
+                _ => None,
+            }
+        };
+        exact
+            .or_else(|| ident.quote_style.is_none().then(folded).flatten())
+            .ok_or_else(|| anyhow!("unknown column {:?}", ident.value))
+    }
+
+    fn function(&mut self, function: &ast::Function) -> Result<Expr> {
+        let name = function.name.to_string().to_ascii_lowercase();
+        let (args, distinct) = match &function.args {
+            FunctionArguments::None => (Vec::new(), false),
+            FunctionArguments::List(list) => (
+                list.args.iter().collect::<Vec<_>>(),
+                matches!(
+                    list.duplicate_treatment,
+                    Some(ast::DuplicateTreatment::Distinct)
+                ),
+            ),
+            FunctionArguments::Subquery(_) => bail!("subqueries are not supported"),
+        };
+
+        let agg = match name.as_str() {
+            "count" => Some(AggFunc::Count),
+            "sum" => Some(AggFunc::Sum),
+            "avg" => Some(AggFunc::Avg),
+            "min" => Some(AggFunc::Min),
+            "max" => Some(AggFunc::Max),
+            _ => None,
+        };
+        if let Some(func) = agg {
+            if !self.allow_aggs {
+                bail!("aggregate function {name}() is not allowed here");
+            }
+            let arg = match args.as_slice() {
+                [FunctionArg::Unnamed(FunctionArgExpr::Wildcard)] if func == AggFunc::Count => None,
+                [FunctionArg::Unnamed(FunctionArgExpr::Expr(e))] => {
+                    // 聚合函数不能嵌套
+                    self.allow_aggs = false;
+                    let arg = self.expr(e);
+                    self.allow_aggs = true;
+                    Some(arg?)
+                }
+                _ => bail!("{name}() expects exactly one argument"),
+            };
+            self.aggs.push(AggCall {
+                func,
+                arg,
+                distinct,
+            });
+            return Ok(Expr::Agg(self.aggs.len() - 1));
+        }
+
+        let scalar = match name.as_str() {
+            "lower" => Scalar::Lower,
+            "upper" => Scalar::Upper,
+            "length" => Scalar::Length,
+            "trim" => Scalar::Trim,
+            "abs" => Scalar::Abs,
+            "round" => Scalar::Round,
+            "coalesce" => Scalar::Coalesce,
+            _ => bail!("unknown function {name}()"),
+        };
+        let args = args
+            .iter()
+            .map(|arg| match arg {
+                FunctionArg::Unnamed(FunctionArgExpr::Expr(e)) => self.expr(e),
+                arg => bail!("unsupported argument {arg}"),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let arity_ok = match scalar {
+            Scalar::Round => (1..=2).contains(&args.len()),
+            Scalar::Coalesce => !args.is_empty(),
+            _ => args.len() == 1,
+        };
+        if !arity_ok {
+            bail!("wrong number of arguments for {name}()");
+        }
+        Ok(Expr::Call(scalar, args))
+    }
+}
+
+fn literal(value: &ast::Value) -> Result<Value> {
+    Ok(match value {
+        ast::Value::Number(n, _) => match n.parse::<i64>() {
+            Ok(i) => Value::from(i),
+            Err(_) => Value::from(n.parse::<f64>()?),
+        },
+        ast::Value::SingleQuotedString(s) | ast::Value::DoubleQuotedString(s) => {
+            Value::String(s.clone())
+        }
+        ast::Value::Boolean(b) => Value::Bool(*b),
+        ast::Value::Null => Value::Null,
+        v => bail!("unsupported literal {v}"),
+    })
+}
+
+/// 将 LIKE 模式转换为正则：`%` 匹配任意字符串，`_` 匹配单个字符
+fn like(pattern: &ast::Expr, case_insensitive: bool) -> Result<Regex> {
+    let ast::Expr::Value(ast::Value::SingleQuotedString(pattern)) = pattern else {
+        bail!("LIKE pattern must be a string literal");
+    };
+    let mut regex = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '%' => regex.push_str(".*"),
+            '_' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Ok(RegexBuilder::new(&regex)
+        .case_insensitive(case_insensitive)
+        .dot_matches_new_line(true)
+        .build()?)
+}
+
+impl Expr {
+    fn eval(&self, row: &[Value], aggs: &[Value]) -> Result<Value> {
+        Ok(match self {
+            Expr::Column(i) => row.get(*i).cloned().unwrap_or(Value::Null),
+            Expr::Literal(v) => v.clone(),
+            Expr::Agg(i) => aggs.get(*i).cloned().unwrap_or(Value::Null),
+            Expr::Not(e) => match e.eval(row, aggs)? {
+                Value::Null => Value::Null,
+                v => Value::Bool(!truthy(&v)),
+            },
+            Expr::Neg(e) => arithmetic(BinOp::Sub, &Value::from(0), &e.eval(row, aggs)?)?,
+            Expr::Binary(lhs, op, rhs) => {
+                let lhs = lhs.eval(row, aggs)?;
+                // AND / OR 短路求值
+                match (op, truthy(&lhs)) {
+                    (BinOp::And, false) if !lhs.is_null() => return Ok(Value::Bool(false)),
+                    (BinOp::Or, true) => return Ok(Value::Bool(true)),
+                    _ => {}
+                }
+                binary(*op, &lhs, &rhs.eval(row, aggs)?)?
+            }
+            Expr::IsNull(e, negated) => Value::Bool(e.eval(row, aggs)?.is_null() != *negated),
+            Expr::InList(e, list, negated) => {
+                let value = e.eval(row, aggs)?;
+                if value.is_null() {
+                    return Ok(Value::Null);
+                }
+                let mut found = false;
+                for item in list {
+                    if compare(&value, &item.eval(row, aggs)?) == Some(Ordering::Equal) {
+                        found = true;
+                        break;
+                    }
+                }
+                Value::Bool(found != *negated)
+            }
+            Expr::Between(e, low, high, negated) => {
+                let value = e.eval(row, aggs)?;
+                match (
+                    compare(&value, &low.eval(row, aggs)?),
+                    compare(&value, &high.eval(row, aggs)?),
+                ) {
+                    (Some(lo), Some(hi)) => {
+                        Value::Bool((lo != Ordering::Less && hi != Ordering::Greater) != *negated)
+                    }
+                    _ => Value::Null,
+                }
+            }
+            Expr::Like(e, regex, negated) => match e.eval(row, aggs)? {
+                Value::Null => Value::Null,
+                v => Value::Bool(regex.is_match(&text(&v)) != *negated),
+            },
+            Expr::Call(scalar, args) => {
+                let args = args
+                    .iter()
+                    .map(|e| e.eval(row, aggs))
+                    .collect::<Result<Vec<_>>>()?;
+                call(*scalar, &args)?
+            }
+        })
+    }
+}
+
+fn binary(op: BinOp, lhs: &Value, rhs: &Value) -> Result<Value> {
+    let cmp = |pred: fn(Ordering) -> bool| match compare(lhs, rhs) {
+        Some(ordering) => Value::Bool(pred(ordering)),
+        None => Value::Null,
+    };
+    Ok(match op {
+        BinOp::Eq => cmp(|o| o == Ordering::Equal),
+        BinOp::Ne => cmp(|o| o != Ordering::Equal),
+        BinOp::Lt => cmp(|o| o == Ordering::Less),
+        BinOp::Le => cmp(|o| o != Ordering::Greater),
+        BinOp::Gt => cmp(|o| o == Ordering::Greater),
+        BinOp::Ge => cmp(|o| o != Ordering::Less),
+        BinOp::And | BinOp::Or if lhs.is_null() || rhs.is_null() => {
+            // 三值逻辑：NULL AND FALSE 为 FALSE，NULL OR TRUE 为 TRUE
+            let known = if lhs.is_null() { rhs } else { lhs };
+            match (op, known.is_null(), truthy(known)) {
+                (BinOp::And, false, false) => Value::Bool(false),
+                (BinOp::Or, false, true) => Value::Bool(true),
+                _ => Value::Null,
+            }
+        }
+        BinOp::And => Value::Bool(truthy(lhs) && truthy(rhs)),
+        BinOp::Or => Value::Bool(truthy(lhs) || truthy(rhs)),
+        BinOp::Concat if lhs.is_null() || rhs.is_null() => Value::Null,
+        BinOp::Concat => Value::String(text(lhs) + &text(rhs)),
+        op => arithmetic(op, lhs, rhs)?,
+    })
+}
+
+/// 整数运算溢出时退化为浮点数；除数为 0 时结果为 NULL
+fn arithmetic(op: BinOp, lhs: &Value, rhs: &Value) -> Result<Value> {
+    if lhs.is_null() || rhs.is_null() {
+        return Ok(Value::Null);
+    }
+    let (Some(a), Some(b)) = (lhs.as_f64(), rhs.as_f64()) else {
+        bail!("arithmetic on non-numeric values {lhs} and {rhs}");
+    };
+    if let (Some(x), Some(y)) = (lhs.as_i64(), rhs.as_i64()) {
+        let result = match op {
+            BinOp::Add => x.checked_add(y),
+            BinOp::Sub => x.checked_sub(y),
+            BinOp::Mul => x.checked_mul(y),
+            BinOp::Div | BinOp::Mod if y == 0 => return Ok(Value::Null),
+            BinOp::Div => x.checked_div(y),
+            BinOp::Mod => x.checked_rem(y),
+            _ => None,
+        };
+        if let Some(result) = result {
+            return Ok(Value::from(result));
+        }
+    }
+    Ok(match op {
+        BinOp::Add => Value::from(a + b),
+        BinOp::Sub => Value::from(a - b),
+        BinOp::Mul => Value::from(a * b),
+        BinOp::Div | BinOp::Mod if b == 0.0 => Value::Null,
+        BinOp::Div => Value::from(a / b),
+        BinOp::Mod => Value::from(a % b),
+        _ => Value::Null,
+    })
+}
+
+fn call(scalar: Scalar, args: &[Value]) -> Result<Value> {
+    let arg = args.first().unwrap_or(&Value::Null);
+    if arg.is_null() && scalar != Scalar::Coalesce {
+        return Ok(Value::Null);
+    }
+    Ok(match scalar {
+        Scalar::Lower => Value::String(text(arg).to_lowercase()),
+        Scalar::Upper => Value::String(text(arg).to_uppercase()),
+        Scalar::Trim => Value::String(text(arg).trim().to_string()),
+        Scalar::Length => Value::from(text(arg).chars().count()),
+        Scalar::Abs => match (arg.as_i64(), arg.as_f64()) {
+            (Some(i), _) => Value::from(i.saturating_abs()),
+            (None, Some(f)) => Value::from(f.abs()),
+            _ => bail!("abs() expects a number, got {arg}"),
+        },
+        Scalar::Round => {
+            let n = arg
+                .as_f64()
+                .ok_or_else(|| anyhow!("round() expects a number, got {arg}"))?;
+            let digits = args.get(1).and_then(Value::as_i64).unwrap_or(0);
+            let factor = 10f64.powi(digits as i32);
+            let rounded = (n * factor).round() / factor;
+            if digits <= 0 && rounded.abs() < i64::MAX as f64 {
+                Value::from(rounded as i64)
+            } else {
+                Value::from(rounded)
+            }
+        }
+        Scalar::Coalesce => args
+            .iter()
+            .find(|v| !v.is_null())
+            .cloned()
+            .unwrap_or(Value::Null),
+    })
+}
+
+impl AggState {
+    fn update(&mut self, call: &AggCall, row: &[Value]) -> Result<()> {
+        let Some(arg) = &call.arg else {
+            self.count += 1;
+            return Ok(());
+        };
+        let value = arg.eval(row, &[])?;
+        if value.is_null() || (call.distinct && !self.seen.insert(value.to_string())) {
+            return Ok(());
+        }
+        self.count += 1;
+        match call.func {
+            AggFunc::Count => {}
+            AggFunc::Sum | AggFunc::Avg => {
+                match (value.as_i64(), value.as_f64()) {
+                    (Some(i), _) if !self.float => match self.int_sum.checked_add(i) {
+                        Some(sum) => self.int_sum = sum,
+                        None => {
+                            self.float = true;
+                            self.float_sum += i as f64;
+                        }
+                    },
+                    (_, Some(f)) => {
+                        self.float = self.float || value.as_i64().is_none();
+                        self.float_sum += f;
+                    }
+                    _ => bail!("sum()/avg() expects numbers, got {value}"),
+                };
+            }
+            AggFunc::Min => {
+                if self
+                    .min
+                    .as_ref()
+                    .is_none_or(|min| compare(&value, min) == Some(Ordering::Less))
+                {
+                    self.min = Some(value);
+                }
+            }
+            AggFunc::Max => {
+                if self
+                    .max
+                    .as_ref()
+                    .is_none_or(|max| compare(&value, max) == Some(Ordering::Greater))
+                {
+                    self.max = Some(value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&self, func: AggFunc) -> Value {
+        let sum = self.int_sum as f64 + self.float_sum;
+        match func {
+            AggFunc::Count => Value::from(self.count),
+            _ if self.count == 0 => Value::Null,
+            AggFunc::Sum if self.float => Value::from(sum),
+            AggFunc::Sum => Value::from(self.int_sum),
+            AggFunc::Avg => Value::from(sum / self.count as f64),
+            AggFunc::Min => self.min.clone().unwrap_or(Value::Null),
+            AggFunc::Max => self.max.clone().unwrap_or(Value::Null),
+        }
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !s.is_empty(),
+        _ => false,
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        v => v.to_string(),
+    }
+}
+
+/// 比较两个值，任一侧为 NULL 时返回 `None`；类型不同时按文本比较
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Null, _) | (_, Value::Null) => None,
+        (Value::Number(x), Value::Number(y)) => x.as_f64()?.partial_cmp(&y.as_f64()?),
+        (Value::Bool(x), Value::Bool(y)) => Some(x.cmp(y)),
+        (a, b) => Some(text(a).cmp(&text(b))),
+    }
+}
+
+/// 排序时 NULL 视为最大值
+fn sort_order(a: &Value, b: &Value) -> Ordering {
+    match (a.is_null(), b.is_null()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        _ => compare(a, b).unwrap_or(Ordering::Equal),
+    }
+}
+
+pub fn process_csv_query(opts: CsvQueryOpts) -> Result<()> {
+    let (table, query) = parse_query(&opts.sql)?;
+    let path = resolve_table(&table, &opts.files)?;
+    let (reader, headers) = dialect_reader(&opts.dialect, &[], open_input(&path)?)?;
+    let plan = Plan::new(&query, &headers)?;
+
+    let rows = reader.into_records().map(|record| {
+        let record = record?;
+        Ok(record.iter().map(infer_value).collect())
+    });
+    let results = plan.execute(rows)?;
+
+    match (&opts.format, &opts.output) {
+        (None, None) => {
+            let mut table = Table::new(plan.names().to_vec())
+                .max_width(40)
+                .color(use_color());
+            for row in &results {
+                table.push_row(row.iter().map(text).collect());
+            }
+            table.render(&mut std::io::stdout().lock())?;
+        }
+        (format, output) => {
+            let format = format.clone().unwrap_or(crate::cli::csv::OutputFormat::Csv);
+            check_unique_headers(
+                plan.names().iter().map(String::as_str),
+                "in the query result, give the expressions distinct aliases with AS",
+            )?;
+            let mut writer = record_writer(&format, get_writer(output.as_deref().unwrap_or("-"))?)?;
+            writer.write_header(&plan.names().iter().collect())?;
+            for row in results.iter() {
+                let map: Map<String, Value> = plan
+                    .names()
+                    .iter()
+                    .cloned()
+                    .zip(row.iter().cloned())
+                    .collect();
+                writer.write_record(&Value::Object(map))?;
+            }
+            writer.finish()?;
+        }
+    }
+    eprintln!("{} rows", results.len());
+
+    Ok(())
+}
+
+/// 表名对应的文件：优先匹配输入文件名（不含扩展名），否则查找当前目录下的 `<表名>.csv`
+fn resolve_table(table: &str, files: &[String]) -> Result<String> {
+    let stem = |file: &String| {
+        Path::new(file)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .map(String::from)
+    };
+    let mut matches = files
+        .iter()
+        .filter(|file| stem(file).is_some_and(|s| s.eq_ignore_ascii_case(table)));
+    match (matches.next(), matches.next()) {
+        (Some(file), None) => Ok(file.clone()),
+        (Some(_), Some(_)) => bail!("table {table:?} matches more than one input file"),
+        _ => {
+            let fallback = format!("{table}.csv");
+            if files.is_empty() && Path::new(&fallback).exists() {
+                return Ok(fallback);
+            }
+            let tables: Vec<_> = files.iter().filter_map(stem).collect();
+            bail!("unknown table {table:?}, available tables: {tables:?}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYERS: &str = "name,club,kit,salary\n\
+                           Buffon,Juventus,77,1500\n\
+                           Dybala,Juventus,10,2000.5\n\
+                           Pogba,United,6,3000\n\
+                           Kean,Juventus,18,\n\
+                           Rashford,United,10,1200\n";
+
+    fn query(sql: &str) -> Result<Vec<Vec<Value>>> {
+        let (table, query) = parse_query(sql)?;
+        assert_eq!(table, "players");
+        let mut reader = csv::Reader::from_reader(PLAYERS.as_bytes());
+        let headers = reader.headers()?.clone();
+        let plan = Plan::new(&query, &headers)?;
+        plan.execute(
+            reader
+                .into_records()
+                .map(|record| Ok(record?.iter().map(infer_value).collect())),
+        )
+    }
+
+    fn json(rows: Vec<Vec<Value>>) -> String {
+        serde_json::to_string(&rows).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_select_where_order_limit() -> Result<()> {
+        let rows = query(
+            "SELECT name, kit * 2 AS double FROM players \
+             WHERE club = 'Juventus' AND salary IS NOT NULL ORDER BY double DESC LIMIT 1",
+        )?;
+        assert_eq!(json(rows), r#"[["Buffon",154]]"#);
+
+        let rows = query("SELECT upper(name) FROM players WHERE name LIKE '%a%' ORDER BY 1")?;
+        assert_eq!(
+            json(rows),
+            r#"[["DYBALA"],["KEAN"],["POGBA"],["RASHFORD"]]"#
+        );
+
+        let rows = query("SELECT DISTINCT kit FROM players WHERE kit IN (10, 6) ORDER BY kit")?;
+        assert_eq!(json(rows), "[[6],[10]]");
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_by_aggregates() -> Result<()> {
+        let rows = query(
+            "SELECT club, count(*), count(salary) AS paid, sum(salary), max(kit) \
+             FROM players GROUP BY club HAVING count(*) > 1 ORDER BY club",
+        )?;
+        assert_eq!(
+            json(rows),
+            r#"[["Juventus",3,2,3500.5,77],["United",2,2,4200,10]]"#
+        );
+
+        let rows = query("SELECT count(DISTINCT kit), avg(kit) FROM players WHERE kit > 100")?;
+        assert_eq!(json(rows), "[[0,null]]");
+
+        let rows = query(
+            "SELECT upper(club), count(*) FROM players GROUP BY upper(club) ORDER BY upper(club)",
+        )?;
+        assert_eq!(json(rows), r#"[["JUVENTUS",3],["UNITED",2]]"#);
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_errors() {
+        assert!(query("SELECT age FROM players").is_err());
+        assert!(query("SELECT name FROM players WHERE count(*) > 1").is_err());
+        for sql in [
+            "SELECT name, count(*) FROM players GROUP BY club",
+            "SELECT club FROM players GROUP BY club HAVING kit > 1",
+            "SELECT club, count(*) FROM players GROUP BY club ORDER BY salary",
+            "SELECT name, max(kit) FROM players",
+        ] {
+            let err = format!("{:#}", query(sql).unwrap_err());
+            assert!(err.contains("must appear in GROUP BY"), "{sql}: {err}");
+        }
+        assert!(query("DELETE FROM players").is_err());
+        assert!(parse_query("SELECT * FROM a JOIN b ON a.id = b.id").is_err());
+    }
+}
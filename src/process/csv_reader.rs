@@ -0,0 +1,280 @@
+use std::{
+    collections::HashSet,
+    io::{BufReader, Cursor, Read},
+};
+
+use anyhow::{anyhow, bail, Result};
+use chardetng::EncodingDetector;
+use csv::{Reader, ReaderBuilder, StringRecord, Trim};
+use encoding_rs::Encoding;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+
+use super::spreadsheet::{is_spreadsheet, sheet_to_csv};
+use crate::{
+    cli::csv::{CsvDialectOpts, CsvReadOpts},
+    utils::get_reader,
+};
+
+pub type CsvInput = BufReader<Box<dyn Read>>;
+
+/// 自动检测编码时读取的样本大小
+const DETECT_SAMPLE: usize = 64 * 1024;
+
+pub fn open_input(input: &str) -> Result<CsvInput> {
+    Ok(BufReader::new(get_reader(input)?))
+}
+
+/// 打开 `opts.input`；电子表格先转换为 csv，之后与普通 csv 走同样的解析流程
+pub fn open_source(opts: &CsvReadOpts) -> Result<CsvInput> {
+    if !is_spreadsheet(&opts.input) {
+        if opts.sheet.is_some() {
+            bail!("--sheet only applies to spreadsheet input");
+        }
+        return open_input(&opts.input);
+    }
+    // 转换出的 csv 总是 UTF-8、双写引号转义且没有注释行，这些选项只会破坏解析
+    let dialect = &opts.dialect;
+    let csv_only = [
+        ("--encoding", dialect.encoding.is_some()),
+        ("--comment", dialect.comment.is_some()),
+        ("--escape", dialect.escape.is_some()),
+        ("--double-quote", !dialect.double_quote),
+    ];
+    if let Some((option, _)) = csv_only.iter().find(|(_, set)| *set) {
+        bail!("{option} does not apply to spreadsheet input");
+    }
+    let csv = sheet_to_csv(
+        &opts.input,
+        opts.sheet.as_deref(),
+        ascii_byte(opts.dialect.delimiter, "delimiter")?,
+        ascii_byte(opts.dialect.quote, "quote")?,
+    )?;
+    Ok(BufReader::new(Box::new(Cursor::new(csv))))
+}
+
+/// 打开 `opts.input` 并读取列名
+pub fn open_csv(opts: &CsvReadOpts) -> Result<(Reader<Box<dyn Read>>, StringRecord)> {
+    csv_reader(opts, open_source(opts)?)
+}
+
+/// 按 `opts` 构建 csv reader 并读取列名，输入统一转码为 UTF-8
+pub fn csv_reader<'a, R: Read + 'a>(
+    opts: &CsvReadOpts,
+    input: R,
+) -> Result<(Reader<Box<dyn Read + 'a>>, StringRecord)> {
+    dialect_reader(&opts.dialect, &opts.columns, input)
+}
+
+/// 按方言参数构建 csv reader，`columns` 非空时覆盖列名
+pub fn dialect_reader<'a, R: Read + 'a>(
+    opts: &CsvDialectOpts,
+    columns: &[String],
+    input: R,
+) -> Result<(Reader<Box<dyn Read + 'a>>, StringRecord)> {
+    let mut reader =
+        reader_builder(opts)?.from_reader(decode_input(opts.encoding.as_deref(), input)?);
+    let headers = read_headers(&mut reader, opts.header, columns)?;
+    Ok((reader, headers))
+}
+
+/// 按方言参数配置 `ReaderBuilder`，不处理编码
+pub fn reader_builder(opts: &CsvDialectOpts) -> Result<ReaderBuilder> {
+    let mut builder = ReaderBuilder::new();
+    builder
+        .has_headers(opts.header)
+        .delimiter(ascii_byte(opts.delimiter, "delimiter")?)
+        .quote(ascii_byte(opts.quote, "quote")?)
+        .escape(opts.escape.map(|c| ascii_byte(c, "escape")).transpose()?)
+        .double_quote(opts.double_quote)
+        .comment(opts.comment.map(|c| ascii_byte(c, "comment")).transpose()?)
+        .flexible(opts.flexible)
+        .trim(if opts.trim { Trim::All } else { Trim::None });
+    Ok(builder)
+}
+
+/// 去除 BOM 并将输入转码为 UTF-8；未指定编码时根据 BOM 判断，无 BOM 则原样透传
+pub fn decode_input<'a, R: Read + 'a>(
+    encoding: Option<&str>,
+    mut input: R,
+) -> Result<Box<dyn Read + 'a>> {
+    let mut sample = Vec::new();
+    let encoding = match encoding {
+        None => None,
+        Some(label) if label.eq_ignore_ascii_case("auto") => {
+            (&mut input)
+                .take(DETECT_SAMPLE as u64)
+                .read_to_end(&mut sample)?;
+            let mut detector = EncodingDetector::new();
+            detector.feed(&sample, sample.len() < DETECT_SAMPLE);
+            Some(detector.guess(None, true))
+        }
+        Some(label) => Some(
+            Encoding::for_label(label.as_bytes())
+                .ok_or_else(|| anyhow!("unknown encoding {label:?}"))?,
+        ),
+    };
+
+    let decoder = DecodeReaderBytesBuilder::new()
+        .encoding(encoding)
+        .bom_override(true)
+        .strip_bom(true)
+        .build(Cursor::new(sample).chain(input));
+    Ok(Box::new(decoder))
+}
+
+pub fn ascii_byte(c: char, name: &str) -> Result<u8> {
+    if c.is_ascii() {
+        Ok(c as u8)
+    } else {
+        bail!("{name} must be an ASCII character, got {c:?}")
+    }
+}
+
+/// 读取列名：优先使用 `columns`，无头部时生成 `col1..colN`
+pub fn read_headers<R: Read>(
+    reader: &mut Reader<R>,
+    header: bool,
+    columns: &[String],
+) -> Result<StringRecord> {
+    // 无头部时 headers() 返回首行数据，只用于确定列数，该行仍会作为记录读出
+    let first = reader.headers()?;
+    if !columns.is_empty() {
+        if columns.len() != first.len() {
+            bail!(
+                "--columns has {} names but input has {} columns",
+                columns.len(),
+                first.len()
+            );
+        }
+        check_unique_headers(columns.iter().map(String::as_str), "in --columns")?;
+        return Ok(StringRecord::from(columns.to_vec()));
+    }
+    if header {
+        check_unique_headers(first, "in the header, rename columns with --columns")?;
+        return Ok(first.clone());
+    }
+    Ok((1..=first.len()).map(|i| format!("col{i}")).collect())
+}
+
+/// 记录按列名输出，重名的列会相互覆盖，因此提前报错；`context` 说明列名来源
+pub fn check_unique_headers<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+    context: &str,
+) -> Result<()> {
+    let mut seen = HashSet::new();
+    for name in names {
+        if !seen.insert(name) {
+            bail!("duplicate column name {name:?} {context}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn read_all(args: &[&str], input: &[u8]) -> Result<Vec<Vec<String>>> {
+        let opts = CsvReadOpts::try_parse_from(["csv", "-i", "-"].iter().chain(args))?;
+        let (mut reader, headers) = csv_reader(&opts, input)?;
+        let mut rows = vec![headers.iter().map(String::from).collect()];
+        for record in reader.records() {
+            rows.push(record?.iter().map(String::from).collect());
+        }
+        Ok(rows)
+    }
+
+    #[test]
+    fn test_strip_utf8_bom() -> Result<()> {
+        let rows = read_all(&[], b"\xEF\xBB\xBFname\nBuffon\n")?;
+        assert_eq!(rows, [["name"], ["Buffon"]]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_transcode_gbk_and_latin1() -> Result<()> {
+        // "姓名\n布冯\n" 的 GBK 编码
+        let gbk = b"\xD0\xD5\xC3\xFB\n\xB2\xBC\xB7\xEB\n";
+        assert_eq!(read_all(&["--encoding", "gbk"], gbk)?, [["姓名"], ["布冯"]]);
+
+        let latin1 = b"name\nHigua\xEDn\n";
+        assert_eq!(
+            read_all(&["--encoding", "latin1"], latin1)?,
+            [["name"], ["Higuaín"]]
+        );
+        assert!(read_all(&["--encoding", "nope"], latin1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_headers() -> Result<()> {
+        let err = read_all(&[], b"a,b,a\n1,2,3\n").unwrap_err();
+        assert!(
+            err.to_string().contains("duplicate column name \"a\""),
+            "{err}"
+        );
+        assert!(read_all(&["--columns", "x,y,x"], b"a,b,a\n1,2,3\n").is_err());
+        assert_eq!(
+            read_all(&["--columns", "a,b,c"], b"a,b,a\n1,2,3\n")?,
+            [["a", "b", "c"], ["1", "2", "3"]]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_spreadsheet_rejects_csv_options() -> Result<()> {
+        let open = |args: &[&str]| {
+            let opts = CsvReadOpts::try_parse_from(
+                ["csv", "-i", "assets/juventus.xlsx", "--sheet", "Roster"]
+                    .iter()
+                    .chain(args),
+            )?;
+            open_csv(&opts).map(|(_, headers)| headers)
+        };
+        let headers = open(&["-d", ";"])?;
+        assert_eq!(&headers[0], "Name");
+        for args in [
+            ["--encoding", "latin1"],
+            ["--comment", "#"],
+            ["--escape", "\\"],
+            ["--double-quote", "false"],
+        ] {
+            let err = open(&args).unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains(&format!("{} does not apply to spreadsheet input", args[0])),
+                "{err}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_reader_options() -> Result<()> {
+        let input = b"# roster\nname;note\n Buffon ;'GK; captain' \nDybala\n";
+        let rows = read_all(
+            &[
+                "-d",
+                ";",
+                "--quote",
+                "'",
+                "--comment",
+                "#",
+                "--trim",
+                "--flexible",
+            ],
+            input,
+        )?;
+        assert_eq!(
+            rows,
+            vec![
+                vec!["name", "note"],
+                vec!["Buffon", "GK; captain"],
+                vec!["Dybala"]
+            ]
+        );
+        Ok(())
+    }
+}
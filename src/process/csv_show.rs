@@ -0,0 +1,86 @@
+use std::{collections::VecDeque, io::Read};
+
+use anyhow::Result;
+use csv::{Reader, StringRecord};
+
+use crate::cli::csv::CsvShowOpts;
+
+use super::{
+    csv_reader::open_csv,
+    table::{use_color, Table},
+};
+
+pub fn process_csv_show(opts: CsvShowOpts) -> Result<()> {
+    let (mut reader, headers) = open_csv(&opts.read)?;
+    let (rows, total) = select_rows(&mut reader, opts.head, opts.tail)?;
+
+    let mut table = Table::new(headers.iter().map(String::from).collect())
+        .max_width(opts.max_width)
+        .color(use_color());
+    let shown = rows.len();
+    rows.into_iter().for_each(|row| table.push_row(row));
+    table.render(&mut std::io::stdout().lock())?;
+
+    if opts.tail.is_some() {
+        eprintln!("last {shown} of {total} rows");
+    } else if opts.head.is_some() {
+        eprintln!("first {shown} rows");
+    }
+
+    Ok(())
+}
+
+/// 按 `--head` / `--tail` 选取要显示的行，返回选中的行与读取的总行数
+///
+/// `--tail` 只保留最后 N 行，内存占用与文件大小无关；`--head` 读够 N 行即停止，
+/// 此时总行数只统计到第 N 行。
+fn select_rows<R: Read>(
+    reader: &mut Reader<R>,
+    head: Option<usize>,
+    tail: Option<usize>,
+) -> Result<(VecDeque<Vec<String>>, usize)> {
+    let mut rows = VecDeque::new();
+    let mut total = 0;
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        if head.is_some_and(|head| rows.len() >= head) {
+            break;
+        }
+        total += 1;
+        rows.push_back(record.iter().map(String::from).collect());
+        if tail.is_some_and(|tail| rows.len() > tail) {
+            rows.pop_front();
+        }
+    }
+    Ok((rows, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYERS: &str = "name\nBuffon\nDybala\nKean\n";
+
+    /// 选中的行以空格连接，便于断言
+    fn select(head: Option<usize>, tail: Option<usize>) -> Result<(String, usize)> {
+        let mut reader = Reader::from_reader(PLAYERS.as_bytes());
+        let (rows, total) = select_rows(&mut reader, head, tail)?;
+        let names: Vec<String> = rows.into_iter().map(|row| row.join(",")).collect();
+        Ok((names.join(" "), total))
+    }
+
+    #[test]
+    fn test_select_rows() -> Result<()> {
+        assert_eq!(select(None, None)?, ("Buffon Dybala Kean".into(), 3));
+        assert_eq!(select(Some(2), None)?, ("Buffon Dybala".into(), 2));
+        assert_eq!(select(None, Some(2))?, ("Dybala Kean".into(), 3));
+        assert_eq!(select(Some(0), None)?, ("".into(), 0));
+        assert_eq!(select(None, Some(0))?, ("".into(), 3));
+        // 超过总行数时显示全部
+        for n in [3, 10] {
+            assert_eq!(select(Some(n), None)?, ("Buffon Dybala Kean".into(), 3));
+            assert_eq!(select(None, Some(n))?, ("Buffon Dybala Kean".into(), 3));
+        }
+        Ok(())
+    }
+}
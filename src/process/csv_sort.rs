@@ -0,0 +1,193 @@
+use std::{
+    cmp::Ordering,
+    fs::File,
+    io::{Read, Seek, Write},
+    mem::size_of,
+};
+
+use anyhow::Result;
+use csv::{Reader, ReaderBuilder, StringRecord, WriterBuilder};
+
+use crate::{
+    cli::csv::CsvSortOpts,
+    utils::{default_output, get_writer},
+};
+
+use super::{
+    csv_filter::resolve_columns, csv_reader::open_csv, csv_types::Schema, csv_writer::record_writer,
+};
+
+type Records = Box<dyn Iterator<Item = Result<StringRecord>>>;
+
+/// 排序规则：按列依次比较，`numeric` 时无法解析为数字的值总是排在最后
+#[derive(Debug)]
+pub struct SortKey {
+    indices: Vec<usize>,
+    desc: bool,
+    numeric: bool,
+}
+
+impl SortKey {
+    pub fn compare(&self, a: &StringRecord, b: &StringRecord) -> Ordering {
+        for &i in &self.indices {
+            let (x, y) = (a.get(i).unwrap_or_default(), b.get(i).unwrap_or_default());
+            let ordering = if self.numeric {
+                match (x.trim().parse::<f64>(), y.trim().parse::<f64>()) {
+                    (Ok(x), Ok(y)) => self.direction(x.total_cmp(&y)),
+                    (Ok(_), Err(_)) => Ordering::Less,
+                    (Err(_), Ok(_)) => Ordering::Greater,
+                    (Err(_), Err(_)) => self.direction(x.cmp(y)),
+                }
+            } else {
+                self.direction(x.cmp(y))
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+
+    fn direction(&self, ordering: Ordering) -> Ordering {
+        if self.desc {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+pub fn process_csv_sort(opts: CsvSortOpts) -> Result<()> {
+    let (reader, headers) = open_csv(&opts.read)?;
+    let input = &opts.read.input;
+    let output = default_output(input, opts.output.as_deref(), &opts.format.to_string());
+    let budget = opts.memory_limit.saturating_mul(1024 * 1024);
+    let rows = sort_csv(&opts, reader, &headers, budget, get_writer(&output)?)?;
+    eprintln!("{input} -> {output}, {rows} rows, Done.");
+
+    Ok(())
+}
+
+/// 稳定排序，返回行数
+///
+/// 内存中的记录超过 `budget` 字节时排序后写入临时文件，
+/// 最后对所有临时文件与内存中剩余的记录做多路归并。
+pub fn sort_csv<R: Read>(
+    opts: &CsvSortOpts,
+    mut reader: Reader<R>,
+    headers: &StringRecord,
+    budget: usize,
+    output: Box<dyn Write>,
+) -> Result<usize> {
+    let key = SortKey {
+        indices: resolve_columns(headers, &opts.by, "--by")?,
+        desc: opts.desc,
+        numeric: opts.numeric,
+    };
+
+    let mut runs = Vec::new();
+    let mut chunk = Vec::new();
+    let mut size = 0;
+    for record in reader.records() {
+        let record = record?;
+        size += record.as_slice().len() + (record.len() + 1) * size_of::<usize>() * 2;
+        chunk.push(record);
+        if size >= budget {
+            runs.push(spill(&mut chunk, &key)?);
+            size = 0;
+        }
+    }
+    chunk.sort_by(|a, b| key.compare(a, b));
+
+    // 按输入先后排列各路，相等时取靠前的一路以保持稳定
+    let mut sources: Vec<Records> = runs
+        .into_iter()
+        .map(|run| Box::new(run.into_records().map(|r| Ok(r?))) as Records)
+        .collect();
+    sources.push(Box::new(chunk.into_iter().map(Ok)));
+    let mut heads = sources
+        .iter_mut()
+        .map(|source| source.next().transpose())
+        .collect::<Result<Vec<_>>>()?;
+
+    let schema = Schema::new(Default::default(), false);
+    let mut writer = record_writer(&opts.format, output)?;
+    writer.write_header(headers)?;
+    let mut rows = 0;
+    loop {
+        let next = heads
+            .iter()
+            .enumerate()
+            .filter_map(|(i, head)| head.as_ref().map(|head| (i, head)))
+            .min_by(|a, b| key.compare(a.1, b.1));
+        let Some((i, record)) = next else {
+            break;
+        };
+        writer.write_record(&schema.record_to_value(headers, record)?)?;
+        heads[i] = sources[i].next().transpose()?;
+        rows += 1;
+    }
+    writer.finish()?;
+
+    Ok(rows)
+}
+
+/// 排序当前块并写入临时文件，返回该文件的 reader
+fn spill(chunk: &mut Vec<StringRecord>, key: &SortKey) -> Result<Reader<File>> {
+    chunk.sort_by(|a, b| key.compare(a, b));
+    let mut writer = WriterBuilder::new()
+        .flexible(true)
+        .from_writer(tempfile::tempfile()?);
+    for record in chunk.drain(..) {
+        writer.write_record(&record)?;
+    }
+    let mut file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.rewind()?;
+    Ok(ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::{process::csv_reader::csv_reader, utils::SharedBuf};
+
+    const PLAYERS: &str = "name,age\nBuffon,41\nDybala,25\nPjanic,29\nKean,\nRugani,25\nCR7,34\n";
+
+    fn sort(args: &[&str], budget: usize) -> Result<String> {
+        let opts = CsvSortOpts::try_parse_from(["sort", "-i", "-"].iter().chain(args))?;
+        let (reader, headers) = csv_reader(&opts.read, PLAYERS.as_bytes())?;
+        let buf = SharedBuf::default();
+        sort_csv(&opts, reader, &headers, budget, Box::new(buf.clone()))?;
+        buf.contents()
+    }
+
+    #[test]
+    fn test_sort_numeric_desc() -> Result<()> {
+        assert_eq!(
+            sort(&["--by", "age", "--numeric", "--desc"], usize::MAX)?,
+            "name,age\nBuffon,41\nCR7,34\nPjanic,29\nDybala,25\nRugani,25\nKean,\n"
+        );
+        assert_eq!(
+            sort(&["--by", "age,name"], usize::MAX)?,
+            "name,age\nKean,\nDybala,25\nRugani,25\nPjanic,29\nCR7,34\nBuffon,41\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_external_sort_matches_in_memory() -> Result<()> {
+        let args = ["--by", "age", "--numeric"];
+        // 每条记录都超出预算，逐条写入临时文件后归并
+        assert_eq!(sort(&args, 1)?, sort(&args, usize::MAX)?);
+        assert_eq!(
+            sort(&args, 40)?,
+            "name,age\nDybala,25\nRugani,25\nPjanic,29\nCR7,34\nBuffon,41\nKean,\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,318 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{Read, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Result};
+use csv::{Reader, StringRecord, Writer, WriterBuilder};
+
+use crate::cli::csv::CsvSplitOpts;
+
+use super::{
+    csv_filter::resolve_columns,
+    csv_reader::{ascii_byte, open_csv},
+};
+
+pub fn process_csv_split(opts: CsvSplitOpts) -> Result<()> {
+    let (reader, headers) = open_csv(&opts.read)?;
+    let input = &opts.read.input;
+    let dir = match &opts.output_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            Path::new(dir).to_path_buf()
+        }
+        None if input == "-" => Path::new(".").to_path_buf(),
+        None => Path::new(input)
+            .parent()
+            .unwrap_or(Path::new("."))
+            .to_path_buf(),
+    };
+
+    let chunks = split_csv(&opts, reader, &headers, |name, append| {
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(append)
+            .truncate(!append)
+            .open(dir.join(name))?;
+        Ok(Box::new(std::io::BufWriter::new(file)))
+    })?;
+    for (name, rows) in &chunks {
+        eprintln!("{} -> {}, {rows} rows", input, dir.join(name).display());
+    }
+    eprintln!("{input}: {} chunks, Done.", chunks.len());
+
+    Ok(())
+}
+
+/// 按行数或列值拆分，每个分块都带表头，返回各分块的 (文件名, 行数)
+///
+/// `create(name, append)` 打开输出，`append` 表示重新打开此前关闭的分块；分块在写入第一行时才创建，
+/// 空输入不产生文件。按行数拆分时同一时刻只打开一个文件，按列拆分时最多打开 `--max-open` 个。
+pub fn split_csv<R: Read>(
+    opts: &CsvSplitOpts,
+    mut reader: Reader<R>,
+    headers: &StringRecord,
+    mut create: impl FnMut(&str, bool) -> Result<Box<dyn Write>>,
+) -> Result<Vec<(String, usize)>> {
+    let prefix = opts.prefix.clone().unwrap_or_else(|| {
+        Path::new(&opts.read.input)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .filter(|s| *s != "-")
+            .unwrap_or("split")
+            .to_string()
+    });
+    let by = opts
+        .by
+        .as_ref()
+        .map(|by| resolve_columns(headers, std::slice::from_ref(by), "--by"))
+        .transpose()?
+        .map(|indices| indices[0]);
+    let limit = match opts.rows {
+        Some(0) => bail!("--rows must be greater than 0"),
+        Some(rows) => rows,
+        None => usize::MAX,
+    };
+    let max_open = match by {
+        Some(_) if opts.max_open == 0 => bail!("--max-open must be greater than 0"),
+        Some(_) => opts.max_open,
+        None => 1,
+    };
+    // 无表头输入拆分后同样不写表头
+    let write_header = opts.read.dialect.header || !opts.read.columns.is_empty();
+    let mut builder = WriterBuilder::new();
+    builder
+        .delimiter(ascii_byte(opts.read.dialect.delimiter, "delimiter")?)
+        .quote(ascii_byte(opts.read.dialect.quote, "quote")?)
+        .flexible(true);
+
+    let mut chunks: Vec<(String, usize)> = Vec::new();
+    // 打开的分块按最近写入排列，最后一个最新
+    let mut open: Vec<(usize, Writer<Box<dyn Write>>)> = Vec::new();
+    let mut values: HashMap<String, usize> = HashMap::new();
+    let mut names = HashSet::new();
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        let idx = match by {
+            Some(col) => {
+                let value = record.get(col).unwrap_or_default();
+                match values.get(value) {
+                    Some(&idx) => idx,
+                    None => {
+                        let name = unique_name(&mut names, &prefix, &file_part(value));
+                        chunks.push((name, 0));
+                        values.insert(value.to_string(), chunks.len() - 1);
+                        chunks.len() - 1
+                    }
+                }
+            }
+            None => {
+                if chunks.last().is_none_or(|(_, rows)| *rows >= limit) {
+                    chunks.push((format!("{prefix}_{:04}.csv", chunks.len() + 1), 0));
+                }
+                chunks.len() - 1
+            }
+        };
+
+        match open.iter().position(|(i, _)| *i == idx) {
+            Some(pos) if pos + 1 < open.len() => {
+                let entry = open.remove(pos);
+                open.push(entry);
+            }
+            Some(_) => {}
+            None => {
+                if open.len() >= max_open {
+                    let (_, mut writer) = open.remove(0);
+                    writer.flush()?;
+                }
+                let (name, rows) = &chunks[idx];
+                let reopen = *rows > 0;
+                let mut writer = builder.from_writer(create(name, reopen)?);
+                if write_header && !reopen {
+                    writer.write_record(headers)?;
+                }
+                open.push((idx, writer));
+            }
+        }
+        if let Some((_, writer)) = open.last_mut() {
+            writer.write_record(&record)?;
+        }
+        chunks[idx].1 += 1;
+    }
+
+    for (_, mut writer) in open {
+        writer.flush()?;
+    }
+    Ok(chunks)
+}
+
+/// 列值转为文件名的一部分：只保留字母、数字、`-`、`_` 与 `.`
+fn file_part(value: &str) -> String {
+    let part: String = value
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match part.trim_matches('.') {
+        "" => "empty".to_string(),
+        part => part.to_string(),
+    }
+}
+
+/// 不同列值转换后可能重名，重名时追加序号
+fn unique_name(names: &mut HashSet<String>, prefix: &str, part: &str) -> String {
+    let mut name = format!("{prefix}_{part}.csv");
+    let mut n = 1;
+    while !names.insert(name.clone()) {
+        n += 1;
+        name = format!("{prefix}_{part}_{n}.csv");
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, collections::BTreeMap, rc::Rc};
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::{process::csv_reader::csv_reader, utils::SharedBuf};
+
+    const PLAYERS: &str = "name,position\n\
+                           Buffon,Goalkeeper\n\
+                           Dybala,Forward\n\
+                           Pjanic,Midfield\n\
+                           Kean,Forward\n\
+                           Bonucci,Centre/Back\n";
+
+    /// 记录同时打开的输出个数
+    struct Tracked {
+        buf: SharedBuf,
+        open: Rc<Cell<(usize, usize)>>,
+    }
+
+    impl Write for Tracked {
+        fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+            self.buf.write(data)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.buf.flush()
+        }
+    }
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            let (current, peak) = self.open.get();
+            self.open.set((current - 1, peak));
+        }
+    }
+
+    /// 返回各文件内容与同时打开文件数的峰值
+    fn split_tracked(args: &[&str], input: &str) -> Result<(BTreeMap<String, String>, usize)> {
+        let opts = CsvSplitOpts::try_parse_from(["split", "-i", "-"].iter().chain(args))?;
+        let (reader, headers) = csv_reader(&opts.read, input.as_bytes())?;
+        let mut files: BTreeMap<String, SharedBuf> = BTreeMap::new();
+        let open = Rc::new(Cell::new((0, 0)));
+        let chunks = split_csv(&opts, reader, &headers, |name, append| {
+            assert_eq!(append, files.contains_key(name), "{name}");
+            let buf = files.entry(name.to_string()).or_default().clone();
+            let (current, peak) = open.get();
+            open.set((current + 1, peak.max(current + 1)));
+            Ok(Box::new(Tracked {
+                buf,
+                open: open.clone(),
+            }))
+        })?;
+        assert_eq!(chunks.len(), files.len());
+        assert_eq!(open.get().0, 0);
+        let files = files
+            .into_iter()
+            .map(|(name, buf)| Ok((name, buf.contents()?)))
+            .collect::<Result<_>>()?;
+        Ok((files, open.get().1))
+    }
+
+    fn split(args: &[&str], input: &str) -> Result<BTreeMap<String, String>> {
+        Ok(split_tracked(args, input)?.0)
+    }
+
+    #[test]
+    fn test_split_by_rows() -> Result<()> {
+        let files = split(&["--rows", "2"], PLAYERS)?;
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            ["split_0001.csv", "split_0002.csv", "split_0003.csv"]
+        );
+        assert_eq!(
+            files["split_0002.csv"],
+            "name,position\nPjanic,Midfield\nKean,Forward\n"
+        );
+        assert_eq!(
+            files["split_0003.csv"],
+            "name,position\nBonucci,Centre/Back\n"
+        );
+
+        let files = split(&["--rows", "2", "--header", "false"], "a,1\nb,2\nc,3\n")?;
+        assert_eq!(files["split_0002.csv"], "c,3\n");
+        assert!(split(&["--rows", "0"], PLAYERS).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_by_column() -> Result<()> {
+        let files = split(&["--by", "position", "--prefix", "juve"], PLAYERS)?;
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            [
+                "juve_Centre_Back.csv",
+                "juve_Forward.csv",
+                "juve_Goalkeeper.csv",
+                "juve_Midfield.csv"
+            ]
+        );
+        assert_eq!(
+            files["juve_Forward.csv"],
+            "name,position\nDybala,Forward\nKean,Forward\n"
+        );
+
+        let files = split(&["--by", "position"], "name,position\na,x/y\nb,x_y\nc,\n")?;
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            ["split_empty.csv", "split_x_y.csv", "split_x_y_2.csv"]
+        );
+        assert!(split(&[], PLAYERS).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_limits_open_files() -> Result<()> {
+        let input: String = std::iter::once("id,group\n".to_string())
+            .chain((0..300).map(|i| format!("{i},g{}\n", i % 7)))
+            .collect();
+
+        let (files, peak) = split_tracked(&["--rows", "2"], &input)?;
+        assert_eq!(files.len(), 150);
+        assert_eq!(peak, 1);
+
+        let (files, peak) = split_tracked(&["--by", "group", "--max-open", "3"], &input)?;
+        assert_eq!(files.len(), 7);
+        assert!(peak <= 3, "{peak}");
+        let expected: String = std::iter::once("id,group\n".to_string())
+            .chain((0..300).filter(|i| i % 7 == 4).map(|i| format!("{i},g4\n")))
+            .collect();
+        assert_eq!(files["split_g4.csv"], expected);
+        assert!(split(&["--by", "group", "--max-open", "0"], &input).is_err());
+        Ok(())
+    }
+}
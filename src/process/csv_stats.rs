@@ -0,0 +1,279 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    io::Write,
+};
+
+use anyhow::Result;
+use csv::StringRecord;
+use serde::Serialize;
+
+use crate::cli::csv::{CsvStatsOpts, StatsFormat};
+
+use super::{
+    csv_reader::open_csv,
+    csv_types::{infer_type, ColumnType},
+    table::{use_color, Table},
+};
+
+/// HyperLogLog 基数估计，2^12 个寄存器，标准误差约 1.6%
+#[derive(Debug, Clone)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+const HLL_BITS: u32 = 12;
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self {
+            registers: vec![0; 1 << HLL_BITS],
+        }
+    }
+}
+
+impl HyperLogLog {
+    pub fn insert(&mut self, value: &str) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        let idx = (hash >> (64 - HLL_BITS)) as usize;
+        let rank = ((hash << HLL_BITS) | (1 << (HLL_BITS - 1))).leading_zeros() as u8 + 1;
+        self.registers[idx] = self.registers[idx].max(rank);
+    }
+
+    pub fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            // 小基数时使用线性计数修正
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            raw.round() as u64
+        }
+    }
+}
+
+/// 单列的统计累加器
+#[derive(Debug)]
+struct ColumnAccumulator {
+    ty: Option<ColumnType>,
+    count: u64,
+    nulls: u64,
+    freq: HashMap<String, u64>,
+    hll: Option<HyperLogLog>,
+    exact_limit: usize,
+    min: f64,
+    max: f64,
+    sum: f64,
+    numbers: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ColumnStats {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: Option<ColumnType>,
+    pub count: u64,
+    pub nulls: u64,
+    pub distinct: u64,
+    /// 不同值个数及高频值是否为估算结果
+    pub approximate: bool,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub mean: Option<f64>,
+    pub top: Vec<TopValue>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TopValue {
+    pub value: String,
+    pub count: u64,
+}
+
+impl ColumnAccumulator {
+    fn new(exact_limit: usize) -> Self {
+        Self {
+            ty: None,
+            count: 0,
+            nulls: 0,
+            freq: HashMap::new(),
+            hll: None,
+            exact_limit,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            sum: 0.0,
+            numbers: 0,
+        }
+    }
+
+    fn add(&mut self, cell: &str) {
+        self.count += 1;
+        let Some(ty) = infer_type(cell) else {
+            self.nulls += 1;
+            return;
+        };
+        self.ty = Some(self.ty.map_or(ty, |t| t.merge(ty)));
+
+        if ty.is_numeric() {
+            if let Ok(n) = cell.parse::<f64>() {
+                self.min = self.min.min(n);
+                self.max = self.max.max(n);
+                self.sum += n;
+                self.numbers += 1;
+            }
+        }
+
+        // 超过精确统计上限后，不同值个数改用 HyperLogLog 估算，
+        // 高频值只在已出现的值中继续计数
+        if let Some(count) = self.freq.get_mut(cell) {
+            *count += 1;
+        } else if self.freq.len() < self.exact_limit {
+            self.freq.insert(cell.to_string(), 1);
+        } else if self.hll.is_none() {
+            let mut hll = HyperLogLog::default();
+            self.freq.keys().for_each(|k| hll.insert(k));
+            self.hll = Some(hll);
+        }
+        if let Some(hll) = self.hll.as_mut() {
+            hll.insert(cell);
+        }
+    }
+
+    fn finish(self, name: &str, top: usize) -> ColumnStats {
+        let numeric = self.ty.is_some_and(ColumnType::is_numeric) && self.numbers > 0;
+        let mut values: Vec<_> = self.freq.into_iter().collect();
+        values.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let top = values
+            .iter()
+            .take(top)
+            .map(|(value, count)| TopValue {
+                value: value.clone(),
+                count: *count,
+            })
+            .collect();
+
+        ColumnStats {
+            name: name.to_string(),
+            ty: self.ty,
+            count: self.count,
+            nulls: self.nulls,
+            distinct: self
+                .hll
+                .as_ref()
+                .map_or(values.len() as u64, HyperLogLog::estimate),
+            approximate: self.hll.is_some(),
+            min: numeric.then_some(self.min),
+            max: numeric.then_some(self.max),
+            mean: numeric.then(|| self.sum / self.numbers as f64),
+            top,
+        }
+    }
+}
+
+pub fn process_csv_stats(opts: CsvStatsOpts) -> Result<()> {
+    let (mut reader, headers) = open_csv(&opts.read)?;
+    let mut columns: Vec<_> = headers
+        .iter()
+        .map(|_| ColumnAccumulator::new(opts.exact_limit))
+        .collect();
+
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        for (column, cell) in columns.iter_mut().zip(record.iter()) {
+            column.add(cell);
+        }
+    }
+
+    let stats: Vec<ColumnStats> = columns
+        .into_iter()
+        .zip(headers.iter())
+        .map(|(column, name)| column.finish(name, opts.top))
+        .collect();
+
+    let mut stdout = std::io::stdout().lock();
+    match opts.format {
+        StatsFormat::Json => {
+            serde_json::to_writer_pretty(&mut stdout, &stats)?;
+            writeln!(stdout)?;
+        }
+        StatsFormat::Table => write_table(&mut stdout, &stats)?,
+    }
+
+    Ok(())
+}
+
+fn write_table(writer: &mut impl Write, stats: &[ColumnStats]) -> Result<()> {
+    let headers = [
+        "column", "type", "nulls", "distinct", "min", "max", "mean", "top",
+    ];
+    let mut table = Table::new(headers.iter().map(|h| h.to_string()).collect())
+        .max_width(60)
+        .color(use_color());
+    let number = |n: Option<f64>| n.map(|n| format!("{n:.2}")).unwrap_or_default();
+    for s in stats {
+        let distinct = if s.approximate {
+            format!("~{}", s.distinct)
+        } else {
+            s.distinct.to_string()
+        };
+        let top = s
+            .top
+            .iter()
+            .map(|t| format!("{}({})", t.value, t.count))
+            .collect::<Vec<_>>()
+            .join(", ");
+        table.push_row(vec![
+            s.name.clone(),
+            s.ty.map(|ty| ty.to_string()).unwrap_or_default(),
+            s.nulls.to_string(),
+            distinct,
+            number(s.min),
+            number(s.max),
+            number(s.mean),
+            top,
+        ]);
+    }
+    table.render(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hyperloglog_estimate() {
+        let mut hll = HyperLogLog::default();
+        for i in 0..50_000 {
+            hll.insert(&i.to_string());
+        }
+        let estimate = hll.estimate() as f64;
+        assert!((estimate - 50_000.0).abs() / 50_000.0 < 0.05, "{estimate}");
+
+        let mut small = HyperLogLog::default();
+        for i in 0..100 {
+            small.insert(&(i % 10).to_string());
+        }
+        assert_eq!(small.estimate(), 10);
+    }
+
+    #[test]
+    fn test_column_stats() {
+        let mut column = ColumnAccumulator::new(2);
+        for cell in ["31", "", "28", "31", "2.5"] {
+            column.add(cell);
+        }
+        let stats = column.finish("age", 1);
+        assert_eq!(stats.ty, Some(ColumnType::Float));
+        assert_eq!((stats.count, stats.nulls), (5, 1));
+        assert_eq!((stats.min, stats.max), (Some(2.5), Some(31.0)));
+        assert_eq!(stats.mean, Some(23.125));
+        assert!(stats.approximate);
+        assert_eq!(stats.distinct, 3);
+        assert_eq!(stats.top[0].value, "31");
+        assert_eq!(stats.top[0].count, 2);
+    }
+}
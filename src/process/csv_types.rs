@@ -0,0 +1,221 @@
+use std::{collections::HashMap, fmt::Display, path::Path};
+
+use anyhow::{anyhow, Result};
+use csv::StringRecord;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+
+use crate::utils::load_document;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    String,
+    Integer,
+    #[serde(alias = "number")]
+    Float,
+    Boolean,
+    Date,
+}
+
+impl ColumnType {
+    /// 合并两列类型：整数与浮点数合并为浮点数，其他不一致时退化为字符串
+    pub fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ColumnType::Integer, ColumnType::Float) | (ColumnType::Float, ColumnType::Integer) => {
+                ColumnType::Float
+            }
+            _ => ColumnType::String,
+        }
+    }
+
+    pub fn is_numeric(self) -> bool {
+        matches!(self, ColumnType::Integer | ColumnType::Float)
+    }
+}
+
+impl Display for ColumnType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColumnType::String => write!(f, "string"),
+            ColumnType::Integer => write!(f, "integer"),
+            ColumnType::Float => write!(f, "float"),
+            ColumnType::Boolean => write!(f, "boolean"),
+            ColumnType::Date => write!(f, "date"),
+        }
+    }
+}
+
+/// 列名到类型的映射，未在 schema 中声明的列按 `infer` 决定是否推断类型
+#[derive(Debug, Default)]
+pub struct Schema {
+    columns: HashMap<String, ColumnType>,
+    infer: bool,
+}
+
+impl Schema {
+    pub fn new(columns: HashMap<String, ColumnType>, infer: bool) -> Self {
+        Self { columns, infer }
+    }
+
+    /// 根据扩展名读取 json / yaml / toml 格式的 schema 文件
+    pub fn load(path: impl AsRef<Path>, infer: bool) -> Result<Self> {
+        Ok(Self::new(load_document(path)?, infer))
+    }
+
+    pub fn column_type(&self, column: &str) -> Option<ColumnType> {
+        self.columns.get(column).copied()
+    }
+
+    pub fn convert(&self, column: &str, cell: &str) -> Result<Value> {
+        match self.column_type(column) {
+            Some(ty) => parse_as(ty, cell)
+                .ok_or_else(|| anyhow!("column {:?}: cannot parse {:?} as {:?}", column, cell, ty)),
+            None if self.infer => Ok(infer_value(cell)),
+            None => Ok(Value::String(cell.to_string())),
+        }
+    }
+
+    pub fn record_to_value(&self, headers: &StringRecord, record: &StringRecord) -> Result<Value> {
+        let mut map = Map::with_capacity(headers.len());
+        for (header, cell) in headers.iter().zip(record.iter()) {
+            map.insert(header.to_string(), self.convert(header, cell)?);
+        }
+        Ok(Value::Object(map))
+    }
+}
+
+/// 推断单元格类型：空值为 null，其后依次尝试布尔、整数、浮点数，其余保留为字符串
+pub fn infer_value(cell: &str) -> Value {
+    infer_type(cell)
+        .and_then(|ty| parse_as(ty, cell))
+        .unwrap_or(Value::Null)
+}
+
+/// 推断单元格类型，空值返回 `None`
+pub fn infer_type(cell: &str) -> Option<ColumnType> {
+    if cell.is_empty() {
+        return None;
+    }
+    if has_leading_zero(cell) {
+        return Some(ColumnType::String);
+    }
+    let ty = [
+        ColumnType::Boolean,
+        ColumnType::Integer,
+        ColumnType::Float,
+        ColumnType::Date,
+    ]
+    .into_iter()
+    .find(|ty| parse_as(*ty, cell).is_some())
+    .unwrap_or(ColumnType::String);
+    Some(ty)
+}
+
+/// 按指定类型解析单元格，空值为 null，无法解析时返回 `None`
+pub fn parse_as(ty: ColumnType, cell: &str) -> Option<Value> {
+    if cell.is_empty() {
+        return Some(Value::Null);
+    }
+    match ty {
+        ColumnType::String => Some(Value::String(cell.to_string())),
+        ColumnType::Boolean => match cell.to_ascii_lowercase().as_str() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        ColumnType::Integer => cell.parse::<i64>().ok().map(Value::from),
+        ColumnType::Float => {
+            if !cell.bytes().any(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            cell.parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number)
+        }
+        ColumnType::Date => is_iso_date(cell).then(|| Value::String(cell.to_string())),
+    }
+}
+
+/// 带前导零的数字（如邮编、编号）应保留为字符串
+fn has_leading_zero(cell: &str) -> bool {
+    let digits = cell.strip_prefix(['-', '+']).unwrap_or(cell).as_bytes();
+    digits.len() > 1 && digits[0] == b'0' && digits[1].is_ascii_digit()
+}
+
+/// 检查 `YYYY-MM-DD`，允许后接 `T` 或空格分隔的时间部分
+pub fn is_iso_date(cell: &str) -> bool {
+    let date = cell.split(['T', ' ']).next().unwrap_or_default();
+    let parts: Vec<&str> = date.split('-').collect();
+    if parts.len() != 3 || parts[0].len() != 4 || parts[1].len() != 2 || parts[2].len() != 2 {
+        return false;
+    }
+    match (
+        parts[0].parse::<u32>(),
+        parts[1].parse::<u32>(),
+        parts[2].parse::<u32>(),
+    ) {
+        (Ok(_), Ok(month), Ok(day)) => (1..=12).contains(&month) && (1..=31).contains(&day),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_infer_value() {
+        assert_eq!(infer_value(""), Value::Null);
+        assert_eq!(infer_value("31"), json!(31));
+        assert_eq!(infer_value("-1.5"), json!(-1.5));
+        assert_eq!(infer_value("TRUE"), json!(true));
+        assert_eq!(infer_value("007"), json!("007"));
+        assert_eq!(infer_value("0.25"), json!(0.25));
+        assert_eq!(infer_value("2019-08-12"), json!("2019-08-12"));
+        assert_eq!(infer_value("NaN"), json!("NaN"));
+        assert_eq!(infer_value("Italy"), json!("Italy"));
+    }
+
+    #[test]
+    fn test_schema_convert() -> Result<()> {
+        let columns = HashMap::from([
+            ("kit".to_string(), ColumnType::String),
+            ("dob".to_string(), ColumnType::Date),
+            ("no".to_string(), ColumnType::Integer),
+        ]);
+        let schema = Schema::new(columns, true);
+        assert_eq!(schema.convert("kit", "7")?, json!("7"));
+        assert_eq!(schema.convert("zip", "007")?, json!("007"));
+        assert_eq!(schema.convert("age", "34")?, json!(34));
+        assert_eq!(schema.convert("no", "007")?, json!(7));
+        assert_eq!(schema.convert("dob", "1985-02-05")?, json!("1985-02-05"));
+        assert!(schema.convert("dob", "Feb 5, 1985").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_infer_type_merge() {
+        let ty = ["1", "2.5", ""]
+            .iter()
+            .filter_map(|cell| infer_type(cell))
+            .reduce(ColumnType::merge);
+        assert_eq!(ty, Some(ColumnType::Float));
+        assert_eq!(
+            ColumnType::Date.merge(ColumnType::Integer),
+            ColumnType::String
+        );
+    }
+
+    #[test]
+    fn test_is_iso_date() {
+        assert!(is_iso_date("2024-02-29"));
+        assert!(is_iso_date("2024-02-29T10:00:00Z"));
+        assert!(!is_iso_date("2024-13-01"));
+        assert!(!is_iso_date("20240101"));
+    }
+}
@@ -0,0 +1,353 @@
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use csv::{Reader, StringRecord};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::{cli::csv::CsvValidateOpts, utils::load_document};
+
+use super::{
+    csv_reader::open_csv,
+    csv_types::{parse_as, ColumnType},
+};
+
+/// 单列的校验规则，字段名与 JSON Schema 保持一致
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RuleSpec {
+    #[serde(rename = "type")]
+    ty: Option<Value>,
+    format: Option<String>,
+    required: bool,
+    pattern: Option<String>,
+    #[serde(rename = "enum")]
+    allowed: Option<Vec<Value>>,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+}
+
+/// 支持的校验关键字
+const KEYWORDS: &[&str] = &[
+    "type", "format", "required", "pattern", "enum", "minimum", "maximum",
+];
+
+/// JSON Schema 中只作说明、不参与校验的关键字
+const ANNOTATIONS: &[&str] = &["title", "description", "$comment", "examples", "default"];
+
+#[derive(Debug)]
+pub struct ColumnRule {
+    name: String,
+    ty: Option<ColumnType>,
+    required: bool,
+    /// 类型中含 `null`，允许空值
+    nullable: bool,
+    pattern: Option<Regex>,
+    allowed: Option<Vec<String>>,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ValidationError {
+    pub line: u64,
+    pub column: String,
+    pub message: String,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, column {:?}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl ColumnRule {
+    fn new(name: &str, spec: RuleSpec) -> Result<Self> {
+        let context = || format!("invalid rule for column {name:?}");
+        let nullable = match &spec.ty {
+            Some(Value::Array(types)) => types.iter().any(|ty| ty == "null"),
+            _ => false,
+        };
+        let ty = match &spec.ty {
+            None => None,
+            Some(Value::String(ty)) => Some(column_type(ty, spec.format.as_deref())?),
+            // JSON Schema 的可空写法：["integer", "null"]
+            Some(Value::Array(types)) => types
+                .iter()
+                .filter_map(Value::as_str)
+                .find(|ty| *ty != "null")
+                .map(|ty| column_type(ty, spec.format.as_deref()))
+                .transpose()?,
+            Some(ty) => bail!("{}: unsupported type {ty}", context()),
+        };
+        let pattern = spec
+            .pattern
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .with_context(context)?;
+        let allowed = spec.allowed.map(|values| {
+            values
+                .into_iter()
+                .map(|v| match v {
+                    Value::String(s) => s,
+                    v => v.to_string(),
+                })
+                .collect()
+        });
+        Ok(Self {
+            name: name.to_string(),
+            ty,
+            required: spec.required,
+            nullable,
+            pattern,
+            allowed,
+            minimum: spec.minimum,
+            maximum: spec.maximum,
+        })
+    }
+
+    /// 校验单元格，返回第一条不满足的规则
+    fn check(&self, cell: &str) -> Option<String> {
+        if cell.is_empty() {
+            return (self.required && !self.nullable)
+                .then(|| "required value is empty".to_string());
+        }
+        if let Some(ty) = self.ty {
+            if parse_as(ty, cell).is_none() {
+                return Some(format!("expected {ty}, got {cell:?}"));
+            }
+        }
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(cell) {
+                return Some(format!("{cell:?} does not match pattern {pattern}"));
+            }
+        }
+        if let Some(allowed) = &self.allowed {
+            if !allowed.iter().any(|v| v == cell) {
+                return Some(format!("{cell:?} is not one of {allowed:?}"));
+            }
+        }
+        if self.minimum.is_some() || self.maximum.is_some() {
+            let Ok(n) = cell.trim().parse::<f64>() else {
+                return Some(format!("expected a number, got {cell:?}"));
+            };
+            if let Some(min) = self.minimum.filter(|min| n < *min) {
+                return Some(format!("{n} is less than minimum {min}"));
+            }
+            if let Some(max) = self.maximum.filter(|max| n > *max) {
+                return Some(format!("{n} is greater than maximum {max}"));
+            }
+        }
+        None
+    }
+}
+
+fn column_type(ty: &str, format: Option<&str>) -> Result<ColumnType> {
+    match (ty, format) {
+        ("string", Some("date") | Some("date-time")) => Ok(ColumnType::Date),
+        (ty, _) => ColumnType::deserialize(Value::String(ty.to_string()))
+            .map_err(|_| anyhow!("unknown type {ty:?}")),
+    }
+}
+
+/// 读取校验规则
+///
+/// 含 `properties` 时按 JSON Schema 解析（`required` 为必填列名列表）；
+/// 否则为列名到规则的映射，规则可以直接写类型名，如 `{"age": "integer"}`。
+pub fn load_rules(path: impl AsRef<Path>) -> Result<Vec<ColumnRule>> {
+    let document: Map<String, Value> = load_document(path)?;
+    let (columns, required) = match document.get("properties") {
+        Some(Value::Object(properties)) => {
+            let required: Vec<&str> = document
+                .get("required")
+                .and_then(Value::as_array)
+                .map(|names| names.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+            (properties, required)
+        }
+        Some(_) => bail!("\"properties\" must be an object"),
+        None => (&document, Vec::new()),
+    };
+
+    columns
+        .iter()
+        .map(|(name, spec)| {
+            let mut spec = match spec {
+                Value::String(ty) => RuleSpec {
+                    ty: Some(Value::String(ty.clone())),
+                    ..Default::default()
+                },
+                spec => {
+                    if let Some(key) = spec.as_object().and_then(|spec| {
+                        spec.keys()
+                            .find(|k| !KEYWORDS.contains(&k.as_str()) && !ANNOTATIONS.contains(&k.as_str()))
+                    }) {
+                        bail!(
+                            "unsupported keyword {key:?} for column {name:?}, supported keywords: {}",
+                            KEYWORDS.join(", ")
+                        );
+                    }
+                    RuleSpec::deserialize(spec)
+                        .with_context(|| format!("invalid rule for column {name:?}"))?
+                }
+            };
+            spec.required |= required.contains(&name.as_str());
+            ColumnRule::new(name, spec)
+        })
+        .collect()
+}
+
+pub fn process_csv_validate(opts: CsvValidateOpts) -> Result<()> {
+    let rules = load_rules(&opts.schema)?;
+    let (reader, headers) = open_csv(&opts.read)?;
+    let input = &opts.read.input;
+
+    let mut stdout = std::io::stdout().lock();
+    let (rows, errors) = validate_csv(&rules, reader, &headers, |error| {
+        Ok(writeln!(stdout, "{input}: {error}")?)
+    })?;
+    if errors > 0 {
+        bail!("{input}: {errors} errors in {rows} rows");
+    }
+    eprintln!("{input}: {rows} rows, valid.");
+
+    Ok(())
+}
+
+/// 逐行校验，每发现一处错误调用一次 `report`，返回 (行数, 错误数)
+pub fn validate_csv<R: Read>(
+    rules: &[ColumnRule],
+    mut reader: Reader<R>,
+    headers: &StringRecord,
+    mut report: impl FnMut(ValidationError) -> Result<()>,
+) -> Result<(usize, usize)> {
+    let mut errors = 0;
+    let mut columns = Vec::new();
+    for rule in rules {
+        match headers.iter().position(|h| h == rule.name) {
+            Some(idx) => columns.push((idx, rule)),
+            None if rule.required => {
+                errors += 1;
+                report(ValidationError {
+                    line: 1,
+                    column: rule.name.clone(),
+                    message: "required column is missing".to_string(),
+                })?;
+            }
+            None => {}
+        }
+    }
+
+    let mut rows = 0;
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        rows += 1;
+        let line = record.position().map_or(0, |pos| pos.line());
+        for (idx, rule) in &columns {
+            if let Some(message) = rule.check(record.get(*idx).unwrap_or_default()) {
+                errors += 1;
+                report(ValidationError {
+                    line,
+                    column: rule.name.clone(),
+                    message,
+                })?;
+            }
+        }
+    }
+
+    Ok((rows, errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::process::csv_reader::csv_reader;
+
+    fn validate(schema: &str, ext: &str, input: &str) -> Result<Vec<String>> {
+        let mut file = tempfile::Builder::new().suffix(ext).tempfile()?;
+        file.write_all(schema.as_bytes())?;
+        let rules = load_rules(file.path())?;
+
+        let opts = CsvValidateOpts::try_parse_from([
+            "validate",
+            "-i",
+            "-",
+            "--schema",
+            &file.path().to_string_lossy(),
+        ])?;
+        let (reader, headers) = csv_reader(&opts.read, input.as_bytes())?;
+        let mut errors = Vec::new();
+        validate_csv(&rules, reader, &headers, |e| {
+            errors.push(e.to_string());
+            Ok(())
+        })?;
+        Ok(errors)
+    }
+
+    #[test]
+    fn test_validate_json_schema() -> Result<()> {
+        let schema = r#"{
+            "type": "object",
+            "required": ["name", "age"],
+            "properties": {
+                "name": {"type": "string", "pattern": "^[A-Z]", "description": "player"},
+                "age": {"type": ["integer", "null"], "minimum": 16, "maximum": 45},
+                "position": {"enum": ["GK", "DF", "MF", "FW"]},
+                "joined": {"type": "string", "format": "date"}
+            }
+        }"#;
+        let input = "name,age,position,joined\n\
+                     Buffon,41,GK,2001-07-03\n\
+                     dybala,,ST,2015-07-01\n\
+                     Kean,15.5,FW,July\n\
+                     ,30,MF,2019-07-01\n";
+        assert_eq!(
+            validate(schema, ".json", input)?,
+            [
+                r#"line 3, column "name": "dybala" does not match pattern ^[A-Z]"#,
+                r#"line 3, column "position": "ST" is not one of ["GK", "DF", "MF", "FW"]"#,
+                r#"line 4, column "age": expected integer, got "15.5""#,
+                r#"line 4, column "joined": expected date, got "July""#,
+                r#"line 5, column "name": required value is empty"#,
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_unknown_keyword() -> Result<()> {
+        for schema in [
+            r#"{"properties": {"name": {"type": "string", "minLength": 2}}}"#,
+            r#"{"age": {"type": "integer", "exclusiveMinimum": 0}}"#,
+        ] {
+            let err = validate(schema, ".json", "name,age\n").unwrap_err();
+            assert!(err.to_string().contains("unsupported keyword"), "{err}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_column_rules() -> Result<()> {
+        let schema = "kit = \"integer\"\n\n[club]\nrequired = true\n";
+        assert_eq!(
+            validate(schema, ".toml", "name,kit\nBuffon,77\nDybala,x\n")?,
+            [
+                r#"line 1, column "club": required column is missing"#,
+                r#"line 3, column "kit": expected integer, got "x""#,
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,444 @@
+use std::{
+    io::{self, Write},
+    mem,
+};
+
+use anyhow::{bail, Result};
+use csv::{StringRecord, Writer, WriterBuilder};
+use serde_json::{Map, Value};
+
+use crate::cli::csv::{Compression, OutputFormat};
+
+#[cfg(feature = "columnar")]
+use super::csv_columnar::ColumnarWriter;
+
+/// 逐条写出记录，内存占用与输入大小无关
+pub trait RecordWriter {
+    fn write_header(&mut self, _headers: &StringRecord) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &Value) -> Result<()>;
+
+    /// 写出缓冲中的记录但不写结尾，用于渲染并行转换的分块片段
+    fn flush(&mut self) -> Result<()>;
+
+    /// 追加由 `fragment_writer` 渲染的片段，`rows` 为其中的记录数
+    fn write_fragment(&mut self, _fragment: &[u8], _rows: usize) -> Result<()> {
+        bail!("this output format cannot be converted in parallel")
+    }
+
+    fn finish(&mut self) -> Result<()>;
+}
+
+pub struct JsonWriter {
+    writer: Box<dyn Write>,
+    count: usize,
+}
+
+pub struct YamlWriter {
+    writer: Box<dyn Write>,
+    count: usize,
+}
+
+pub struct TomlWriter {
+    writer: Box<dyn Write>,
+    count: usize,
+}
+
+pub struct NdjsonWriter {
+    writer: Box<dyn Write>,
+}
+
+pub struct DelimitedWriter {
+    writer: Writer<Box<dyn Write>>,
+    headers: StringRecord,
+    delimiter: u8,
+}
+
+impl JsonWriter {
+    pub fn new(writer: Box<dyn Write>) -> Self {
+        Self { writer, count: 0 }
+    }
+}
+
+impl YamlWriter {
+    pub fn new(writer: Box<dyn Write>) -> Self {
+        Self { writer, count: 0 }
+    }
+}
+
+impl TomlWriter {
+    pub fn new(writer: Box<dyn Write>) -> Self {
+        Self { writer, count: 0 }
+    }
+}
+
+impl NdjsonWriter {
+    pub fn new(writer: Box<dyn Write>) -> Self {
+        Self { writer }
+    }
+}
+
+impl DelimitedWriter {
+    pub fn new(writer: Box<dyn Write>, delimiter: u8) -> Self {
+        let writer = WriterBuilder::new()
+            .delimiter(delimiter)
+            .from_writer(writer);
+        Self {
+            writer,
+            headers: StringRecord::new(),
+            delimiter,
+        }
+    }
+}
+
+impl RecordWriter for JsonWriter {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        let sep = if self.count == 0 { "[" } else { "," };
+        let item = serde_json::to_string_pretty(record)?.replace('\n', "\n  ");
+        write!(self.writer, "{sep}\n  {item}")?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn write_fragment(&mut self, fragment: &[u8], rows: usize) -> Result<()> {
+        // 片段以 `,` 开头，作为第一批记录时换成 `[`
+        match (rows, self.count) {
+            (0, _) => return Ok(()),
+            (_, 0) => {
+                self.writer.write_all(b"[")?;
+                self.writer.write_all(&fragment[1..])?;
+            }
+            _ => self.writer.write_all(fragment)?,
+        }
+        self.count += rows;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.count == 0 {
+            writeln!(self.writer, "[]")?;
+        } else {
+            writeln!(self.writer, "\n]")?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl RecordWriter for YamlWriter {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        // 单元素序列拼接后仍是同一个 yaml 列表
+        serde_yaml::to_writer(&mut self.writer, &[record])?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn write_fragment(&mut self, fragment: &[u8], rows: usize) -> Result<()> {
+        self.writer.write_all(fragment)?;
+        self.count += rows;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        // 空文档会被解析为 null，没有记录时写出空列表
+        if self.count == 0 {
+            writeln!(self.writer, "[]")?;
+        }
+        self.flush()
+    }
+}
+
+impl RecordWriter for TomlWriter {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        // toml 不支持顶层数组与 null，每条记录去掉空值后写成一个 [[records]] 表
+        let record = toml_value(record);
+        let mut root = Map::new();
+        root.insert("records".to_string(), Value::Array(vec![record]));
+        if self.count > 0 {
+            writeln!(self.writer)?;
+        }
+        self.writer.write_all(toml::to_string(&root)?.as_bytes())?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn write_fragment(&mut self, fragment: &[u8], rows: usize) -> Result<()> {
+        // 片段以分隔记录的空行开头，作为第一批记录时去掉
+        match (rows, self.count) {
+            (0, _) => return Ok(()),
+            (_, 0) => self.writer.write_all(&fragment[1..])?,
+            _ => self.writer.write_all(fragment)?,
+        }
+        self.count += rows;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.count == 0 {
+            writeln!(self.writer, "records = []")?;
+        }
+        self.flush()
+    }
+}
+
+/// 递归去掉对象中的 null；数组中的 null 换成空字符串，以保留其余元素的下标
+fn toml_value(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k.clone(), toml_value(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|v| match v {
+                    Value::Null => Value::String(String::new()),
+                    v => toml_value(v),
+                })
+                .collect(),
+        ),
+        v => v.clone(),
+    }
+}
+
+impl RecordWriter for NdjsonWriter {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn write_fragment(&mut self, fragment: &[u8], _rows: usize) -> Result<()> {
+        self.writer.write_all(fragment)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.flush()
+    }
+}
+
+impl RecordWriter for DelimitedWriter {
+    fn write_header(&mut self, headers: &StringRecord) -> Result<()> {
+        self.headers = headers.clone();
+        self.writer.write_record(headers)?;
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        for h in self.headers.iter() {
+            match record.get(h) {
+                Some(Value::String(s)) => self.writer.write_field(s)?,
+                Some(Value::Null) | None => self.writer.write_field("")?,
+                Some(v) => self.writer.write_field(v.to_string())?,
+            }
+        }
+        self.writer.write_record(None::<&[u8]>)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn write_fragment(&mut self, fragment: &[u8], _rows: usize) -> Result<()> {
+        // csv::Writer 不提供内部输出的可变引用，取出后写入片段再重新包装
+        let placeholder = WriterBuilder::new().from_writer(Box::new(io::sink()) as Box<dyn Write>);
+        let mut inner = mem::replace(&mut self.writer, placeholder)
+            .into_inner()
+            .map_err(|e| e.into_error())?;
+        inner.write_all(fragment)?;
+        self.writer = WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .from_writer(inner);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.flush()
+    }
+}
+
+/// 列式格式（parquet / arrow）的写出参数
+#[derive(Debug, Clone)]
+pub struct WriterOptions {
+    pub row_group_size: usize,
+    pub compression: Compression,
+}
+
+impl Default for WriterOptions {
+    fn default() -> Self {
+        Self {
+            row_group_size: 65536,
+            compression: Compression::Snappy,
+        }
+    }
+}
+
+pub fn record_writer(
+    format: &OutputFormat,
+    writer: Box<dyn Write>,
+) -> Result<Box<dyn RecordWriter>> {
+    record_writer_with(format, writer, &WriterOptions::default())
+}
+
+pub fn record_writer_with(
+    format: &OutputFormat,
+    writer: Box<dyn Write>,
+    options: &WriterOptions,
+) -> Result<Box<dyn RecordWriter>> {
+    Ok(match format {
+        OutputFormat::Json => Box::new(JsonWriter::new(writer)),
+        OutputFormat::Yaml => Box::new(YamlWriter::new(writer)),
+        OutputFormat::Toml => Box::new(TomlWriter::new(writer)),
+        OutputFormat::Ndjson => Box::new(NdjsonWriter::new(writer)),
+        OutputFormat::Tsv => Box::new(DelimitedWriter::new(writer, b'\t')),
+        OutputFormat::Csv => Box::new(DelimitedWriter::new(writer, b',')),
+        #[cfg(feature = "columnar")]
+        OutputFormat::Parquet | OutputFormat::Arrow => {
+            Box::new(ColumnarWriter::new(format.clone(), writer, options.clone()))
+        }
+        #[cfg(not(feature = "columnar"))]
+        OutputFormat::Parquet | OutputFormat::Arrow => {
+            let _ = options;
+            bail!("{format} output requires rcli to be built with `--features columnar`")
+        }
+    })
+}
+
+/// 并行转换时渲染单个分块的写出器：接在之前的记录后继续写，不写表头与结尾
+///
+/// 各分块的输出按顺序交给主写出器的 `write_fragment`，结果与顺序转换一致。
+pub fn fragment_writer(
+    format: &OutputFormat,
+    writer: Box<dyn Write>,
+    headers: &StringRecord,
+) -> Result<Box<dyn RecordWriter>> {
+    Ok(match format {
+        OutputFormat::Json => Box::new(JsonWriter { writer, count: 1 }),
+        OutputFormat::Toml => Box::new(TomlWriter { writer, count: 1 }),
+        OutputFormat::Yaml => Box::new(YamlWriter::new(writer)),
+        OutputFormat::Ndjson => Box::new(NdjsonWriter::new(writer)),
+        OutputFormat::Tsv | OutputFormat::Csv => {
+            let delimiter = if matches!(format, OutputFormat::Tsv) {
+                b'\t'
+            } else {
+                b','
+            };
+            let mut writer = DelimitedWriter::new(writer, delimiter);
+            writer.headers = headers.clone();
+            Box::new(writer)
+        }
+        OutputFormat::Parquet | OutputFormat::Arrow => {
+            bail!("{format} output cannot be converted in parallel")
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::SharedBuf;
+
+    fn render(format: OutputFormat, records: &[Value]) -> Result<String> {
+        let buf = SharedBuf::default();
+        let mut writer = record_writer(&format, Box::new(buf.clone()))?;
+        writer.write_header(&StringRecord::from(vec!["name", "club"]))?;
+        for record in records {
+            writer.write_record(record)?;
+        }
+        writer.finish()?;
+        buf.contents()
+    }
+
+    fn sample() -> Vec<Value> {
+        vec![
+            serde_json::json!({"name": "Buffon", "club": "Juventus"}),
+            serde_json::json!({"name": "Dybala", "club": "Juventus"}),
+        ]
+    }
+
+    #[test]
+    fn test_json_writer() -> Result<()> {
+        let records = sample();
+        let out = render(OutputFormat::Json, &records)?;
+        assert_eq!(out, serde_json::to_string_pretty(&records)? + "\n");
+        assert_eq!(render(OutputFormat::Json, &[])?, "[]\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_yaml_writer() -> Result<()> {
+        let records = sample();
+        let out = render(OutputFormat::Yaml, &records)?;
+        assert_eq!(out, serde_yaml::to_string(&records)?);
+        assert_eq!(render(OutputFormat::Yaml, &[])?, "[]\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_ndjson_writer() -> Result<()> {
+        assert_eq!(
+            render(OutputFormat::Ndjson, &sample())?,
+            "{\"name\":\"Buffon\",\"club\":\"Juventus\"}\n{\"name\":\"Dybala\",\"club\":\"Juventus\"}\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_tsv_writer() -> Result<()> {
+        assert_eq!(
+            render(OutputFormat::Tsv, &sample())?,
+            "name\tclub\nBuffon\tJuventus\nDybala\tJuventus\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_writer() -> Result<()> {
+        let out = render(OutputFormat::Toml, &sample())?;
+        let parsed: toml::Value = toml::from_str(&out)?;
+        assert_eq!(parsed["records"][1]["name"].as_str(), Some("Dybala"));
+        let parsed: toml::Value = toml::from_str(&render(OutputFormat::Toml, &[])?)?;
+        assert_eq!(parsed["records"].as_array().map(Vec::len), Some(0));
+
+        let nested = serde_json::json!({
+            "name": "Dybala",
+            "address": {"city": null, "country": "Argentina"},
+            "tags": ["fw", null, {"no": null}],
+        });
+        let parsed: toml::Value = toml::from_str(&render(OutputFormat::Toml, &[nested])?)?;
+        let record = &parsed["records"][0];
+        assert!(record["address"].get("city").is_none());
+        assert_eq!(record["address"]["country"].as_str(), Some("Argentina"));
+        assert_eq!(record["tags"][0].as_str(), Some("fw"));
+        assert_eq!(record["tags"][1].as_str(), Some(""));
+        assert_eq!(record["tags"][2].as_table().map(|t| t.len()), Some(0));
+        Ok(())
+    }
+}
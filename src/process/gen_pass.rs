@@ -1,44 +1,493 @@
-use anyhow::Result;
-use rand::prelude::SliceRandom;
+use std::{collections::HashSet, fs, io::Write};
 
-use crate::cli::genpass::GenPassOpts;
+use anyhow::{bail, Context, Result};
+use rand::{prelude::SliceRandom, Rng};
+use serde::Serialize;
+use zxcvbn::zxcvbn;
 
-const UPPER: &[u8] = b"ABCDEFGHJKLMNOPQRSTUVWXYZ";
-const LOWER: &[u8] = b"abcdefghijkmnopqrstuvwxyz";
-const NUMBER: &[u8] = b"0123456789";
-const SYMBOL: &[u8] = b"!@#$%^&*_";
+use crate::cli::genpass::{GenPassFormat, GenPassOpts};
 
+use super::pass_policy::PasswordPolicy;
+
+const UPPER: &str = "ABCDEFGHJKLMNOPQRSTUVWXYZ";
+const LOWER: &str = "abcdefghijkmnopqrstuvwxyz";
+const NUMBER: &str = "0123456789";
+const SYMBOL: &str = "!@#$%^&*_";
+
+const EFF_LARGE: &str = include_str!("../../assets/eff_large_wordlist.txt");
+const EFF_SHORT: &str = include_str!("../../assets/eff_short_wordlist_1.txt");
+
+/// 一个生成结果：密码、按生成方式估算的熵，以及 zxcvbn 的评分与建议
+#[derive(Debug, Serialize)]
+pub struct PassEntry {
+    pub password: String,
+    pub entropy_bits: f64,
+    pub score: u8,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+/// 候选字符类别或词表，批量生成时只准备一次
+enum Source {
+    Chars(Vec<Vec<char>>),
+    Words(Vec<String>),
+}
+
+pub struct Generator<'a> {
+    opts: &'a GenPassOpts,
+    source: Source,
+    policy: Option<PasswordPolicy>,
+}
+
+/// 生成随机密码，每个启用的字符类别至少出现一次；指定 `--words` 时生成口令短语
+///
+/// 指定 `--policy` 时重新生成直到满足策略，最多尝试 `--attempts` 次。
 pub fn process_genpass(opts: GenPassOpts) -> Result<String> {
-    let mut rng = rand::thread_rng();
-    let mut password = Vec::new();
-    let mut chars = Vec::new();
+    Generator::new(&opts)?.generate()
+}
 
-    if opts.uppercase {
-        chars.extend_from_slice(UPPER);
-        password.push(*UPPER.choose(&mut rng).unwrap());
+/// 按 `--count` 批量生成并评估
+pub fn process_genpass_entries(opts: &GenPassOpts) -> Result<Vec<PassEntry>> {
+    if opts.count == 0 {
+        bail!("--count must be greater than 0");
     }
-    if opts.lowercase {
-        chars.extend_from_slice(LOWER);
-        password.push(*LOWER.choose(&mut rng).unwrap());
+    let generator = Generator::new(opts)?;
+    let entropy_bits = generator.entropy_bits();
+    (0..opts.count)
+        .map(|_| Ok(evaluate(generator.generate()?, entropy_bits)))
+        .collect()
+}
+
+impl<'a> Generator<'a> {
+    pub fn new(opts: &'a GenPassOpts) -> Result<Self> {
+        let source = match opts.words {
+            Some(0) => bail!("--words must be greater than 0"),
+            Some(_) => Source::Words(load_wordlist(&opts.wordlist)?),
+            None => {
+                let classes = char_classes(opts)?;
+                let length = opts.length as usize;
+                if length < classes.len() {
+                    bail!(
+                        "length {length} is too short to include all {} enabled character classes",
+                        classes.len()
+                    );
+                }
+                let available: usize = classes.iter().map(Vec::len).sum();
+                if opts.no_repeat && length > available {
+                    bail!(
+                        "--no-repeat needs {length} distinct characters, but only {available} are available"
+                    );
+                }
+                Source::Chars(classes)
+            }
+        };
+        let policy = opts.policy.as_ref().map(PasswordPolicy::load).transpose()?;
+        if let Some(policy) = &policy {
+            if opts.words.is_none() && (opts.length as usize) < policy.min_length() {
+                bail!(
+                    "length {} is shorter than the {} characters required by the policy",
+                    opts.length,
+                    policy.min_length()
+                );
+            }
+        }
+        Ok(Self {
+            opts,
+            source,
+            policy,
+        })
     }
-    if opts.number {
-        chars.extend_from_slice(NUMBER);
-        password.push(*NUMBER.choose(&mut rng).unwrap());
+
+    pub fn generate(&self) -> Result<String> {
+        match &self.policy {
+            Some(policy) => policy.generate(self.opts.attempts, || Ok(self.generate_once())),
+            None => Ok(self.generate_once()),
+        }
     }
-    if opts.symbol {
-        chars.extend_from_slice(SYMBOL);
-        password.push(*SYMBOL.choose(&mut rng).unwrap());
+
+    /// 按均匀抽样估算的熵（比特）；每类至少一个字符与策略筛选带来的少量损失不计入
+    pub fn entropy_bits(&self) -> f64 {
+        let opts = self.opts;
+        match &self.source {
+            Source::Chars(classes) => {
+                let n: usize = classes.iter().map(Vec::len).sum();
+                let length = opts.length as usize;
+                if opts.no_repeat {
+                    (0..length).map(|i| ((n - i) as f64).log2()).sum()
+                } else {
+                    length as f64 * (n as f64).log2()
+                }
+            }
+            Source::Words(list) => {
+                let words = opts.words.unwrap_or_default();
+                let mut bits = words as f64 * (list.len() as f64).log2();
+                if opts.digit {
+                    bits += (10.0 * words as f64).log2();
+                }
+                bits
+            }
+        }
     }
 
-    for _ in 0..(opts.length - password.len() as u8) {
-        // let idx = rng.gen_range(0..chars.len());
-        // password.push(chars[idx] as char);
+    fn generate_once(&self) -> String {
+        match &self.source {
+            Source::Chars(classes) => password(self.opts, classes),
+            Source::Words(list) => passphrase(self.opts, list),
+        }
+    }
+}
 
-        password.push(*chars.choose(&mut rng).unwrap());
+fn password(opts: &GenPassOpts, classes: &[Vec<char>]) -> String {
+    let length = opts.length as usize;
+    let mut rng = rand::thread_rng();
+    let mut chars: Vec<char> = classes.concat();
+    let mut password = Vec::with_capacity(length);
+    for class in classes {
+        password.extend(class.choose(&mut rng));
+    }
+    if opts.no_repeat {
+        // 各类别互不相交，已选字符从候选中去掉后不放回地抽取
+        chars.retain(|c| !password.contains(c));
+        chars.shuffle(&mut rng);
+        password.extend(chars.into_iter().take(length - password.len()));
+    } else {
+        while password.len() < length {
+            password.extend(chars.choose(&mut rng));
+        }
     }
     password.shuffle(&mut rng);
 
-    let pwd = String::from_utf8(password)?;
+    password.into_iter().collect()
+}
+
+fn evaluate(password: String, entropy_bits: f64) -> PassEntry {
+    let estimate = zxcvbn(&password, &[]);
+    let feedback = estimate.feedback();
+    PassEntry {
+        entropy_bits: (entropy_bits * 100.0).round() / 100.0,
+        score: estimate.score().into(),
+        warning: feedback.and_then(|f| f.warning()).map(|w| w.to_string()),
+        suggestions: feedback
+            .map(|f| f.suggestions().iter().map(|s| s.to_string()).collect())
+            .unwrap_or_default(),
+        password,
+    }
+}
+
+/// 按格式输出：plain 每行一个密码，json 为对象数组，csv 的建议以空格连接
+pub fn write_entries(
+    entries: &[PassEntry],
+    format: GenPassFormat,
+    writer: &mut impl Write,
+) -> Result<()> {
+    match format {
+        GenPassFormat::Plain => {
+            for entry in entries {
+                writeln!(writer, "{}", entry.password)?;
+            }
+        }
+        GenPassFormat::Json => {
+            serde_json::to_writer_pretty(&mut *writer, entries)?;
+            writeln!(writer)?;
+        }
+        GenPassFormat::Csv => {
+            let mut csv = csv::Writer::from_writer(&mut *writer);
+            csv.write_record([
+                "password",
+                "entropy_bits",
+                "score",
+                "warning",
+                "suggestions",
+            ])?;
+            for entry in entries {
+                csv.write_record([
+                    entry.password.as_str(),
+                    &entry.entropy_bits.to_string(),
+                    &entry.score.to_string(),
+                    entry.warning.as_deref().unwrap_or_default(),
+                    &entry.suggestions.join(" "),
+                ])?;
+            }
+            csv.flush()?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// 生效的字符类别：去重、去掉排除字符后非空的类别，类别之间互不相交
+fn char_classes(opts: &GenPassOpts) -> Result<Vec<Vec<char>>> {
+    let classes: Vec<Vec<char>> = match &opts.charset {
+        Some(spec) => {
+            let mut groups = vec![Vec::new(); 4];
+            for c in parse_charset(spec)? {
+                let idx = if c.is_uppercase() {
+                    0
+                } else if c.is_lowercase() {
+                    1
+                } else if c.is_numeric() {
+                    2
+                } else {
+                    3
+                };
+                groups[idx].push(c);
+            }
+            groups
+        }
+        None => {
+            let symbols = match &opts.symbols {
+                Some(spec) => parse_charset(spec)?,
+                None => SYMBOL.chars().collect(),
+            };
+            [
+                (!opts.no_uppercase, UPPER.chars().collect()),
+                (!opts.no_lowercase, LOWER.chars().collect()),
+                (!opts.no_number, NUMBER.chars().collect()),
+                (!opts.no_symbol, symbols),
+            ]
+            .into_iter()
+            .filter_map(|(enabled, class)| enabled.then_some(class))
+            .collect()
+        }
+    };
+    if classes.is_empty() {
+        bail!("all character classes are disabled, nothing to generate from");
+    }
+
+    let mut seen: HashSet<char> = opts.exclude.iter().flat_map(|s| s.chars()).collect();
+    let classes: Vec<Vec<char>> = classes
+        .into_iter()
+        .map(|class| class.into_iter().filter(|c| seen.insert(*c)).collect())
+        .filter(|class: &Vec<char>| !class.is_empty())
+        .collect();
+    if classes.is_empty() {
+        bail!("no characters left to generate from after --exclude");
+    }
+    Ok(classes)
+}
+
+/// 解析字符集：`a-z` 表示范围，开头或结尾的 `-` 按字面处理
+fn parse_charset(spec: &str) -> Result<Vec<char>> {
+    let chars: Vec<char> = spec.chars().collect();
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if i + 2 < chars.len() && chars[i + 1] == '-' {
+            let (from, to) = (chars[i], chars[i + 2]);
+            if from > to {
+                bail!("invalid range {from}-{to} in charset {spec:?}");
+            }
+            result.extend(from..=to);
+            i += 3;
+        } else {
+            result.push(chars[i]);
+            i += 1;
+        }
+    }
+    if result.is_empty() {
+        bail!("charset must not be empty");
+    }
+    Ok(result)
+}
+
+fn passphrase(opts: &GenPassOpts, list: &[String]) -> String {
+    let words = opts.words.unwrap_or_default();
+    let mut rng = rand::thread_rng();
+    let mut chosen: Vec<String> = (0..words)
+        .filter_map(|_| list.choose(&mut rng).cloned())
+        .collect();
+    if opts.capitalize {
+        for word in chosen.iter_mut() {
+            let mut chars = word.chars();
+            if let Some(first) = chars.next() {
+                *word = first.to_uppercase().chain(chars).collect();
+            }
+        }
+    }
+    if opts.digit {
+        let i = rng.gen_range(0..chosen.len());
+        chosen[i].push(char::from(b'0' + rng.gen_range(0..10)));
+    }
+
+    chosen.join(&opts.separator)
+}
+
+/// 读取词表：内置名称或文件路径
+///
+/// 每行取最后一个字段，兼容 EFF 的 `11111\tabacus` 格式；空行与 `#` 开头的行被忽略，重复单词只保留一个。
+pub fn load_wordlist(name: &str) -> Result<Vec<String>> {
+    let content = match name {
+        "eff-large" => EFF_LARGE.to_string(),
+        "eff-short" => EFF_SHORT.to_string(),
+        path => {
+            fs::read_to_string(path).with_context(|| format!("cannot read wordlist {path:?}"))?
+        }
+    };
+    let mut seen = HashSet::new();
+    let words: Vec<String> = content
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| line.split_whitespace().last())
+        .filter(|word| seen.insert(*word))
+        .map(String::from)
+        .collect();
+    if words.len() < 2 {
+        bail!("wordlist {name:?} needs at least 2 distinct words");
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn genpass(args: &[&str]) -> Result<String> {
+        process_genpass(GenPassOpts::try_parse_from(["genpass"].iter().chain(args))?)
+    }
+
+    #[test]
+    fn test_genpass_classes() -> Result<()> {
+        let pwd = genpass(&[])?;
+        assert_eq!(pwd.len(), 16);
+        for class in [UPPER, LOWER, NUMBER, SYMBOL] {
+            assert!(pwd.chars().any(|c| class.contains(c)), "{pwd}");
+        }
+
+        let pwd = genpass(&["-l", "32", "--no-symbol", "--no-uppercase"])?;
+        assert_eq!(pwd.len(), 32);
+        assert!(pwd.chars().all(|c| LOWER.contains(c) || NUMBER.contains(c)));
+
+        let pwd = genpass(&["-l", "1", "--no-lowercase", "--no-number", "--no-symbol"])?;
+        assert!(UPPER.contains(&pwd));
+        Ok(())
+    }
+
+    #[test]
+    fn test_genpass_custom_charset() -> Result<()> {
+        assert_eq!(parse_charset("a-c0-2")?, ['a', 'b', 'c', '0', '1', '2']);
+        assert_eq!(parse_charset("!@#-")?, ['!', '@', '#', '-']);
+        assert_eq!(parse_charset("-a")?, ['-', 'a']);
+        assert!(parse_charset("z-a").is_err());
+
+        let pwd = genpass(&["-l", "24", "--charset", "a-f0-9"])?;
+        assert!(pwd
+            .chars()
+            .all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+        assert!(pwd.chars().any(|c| c.is_ascii_digit()), "{pwd}");
 
-    Ok(pwd)
+        let pwd = genpass(&["-l", "40", "--symbols", "-.", "--exclude", "O0Il1"])?;
+        assert!(pwd.chars().any(|c| c == '-' || c == '.'), "{pwd}");
+        assert!(!pwd.chars().any(|c| "O0Il1!@#$%^&*_".contains(c)), "{pwd}");
+
+        let pwd = genpass(&["-l", "10", "--charset", "0-9", "--no-repeat"])?;
+        let mut digits: Vec<char> = pwd.chars().collect();
+        digits.sort();
+        assert_eq!(digits.into_iter().collect::<String>(), "0123456789");
+
+        assert!(genpass(&["-l", "11", "--charset", "0-9", "--no-repeat"]).is_err());
+        assert!(genpass(&["--charset", "abc", "--exclude", "cba"]).is_err());
+        assert!(genpass(&["--charset", "a-z", "--no-symbol"]).is_err());
+        assert!(genpass(&["--words", "3", "--exclude", "a"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_genpass_policy() -> Result<()> {
+        let mut file = tempfile::Builder::new().suffix(".toml").tempfile()?;
+        std::io::Write::write_all(
+            &mut file,
+            b"min_digits = 2\nmin_symbols = 3\nmax_consecutive = 1\nmin_score = 3\n",
+        )?;
+        let path = file.path().to_string_lossy().to_string();
+        let pwd = genpass(&["--policy", &path])?;
+        let policy = PasswordPolicy::load(&path)?;
+        assert_eq!(policy.check(&pwd), None, "{pwd}");
+
+        // 只有字母时永远满足不了数字的要求
+        let err = genpass(&["--policy", &path, "--charset", "a-z", "--attempts", "10"])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("at least 2 digits"), "{err}");
+        assert!(genpass(&["--policy", &path, "-l", "4"]).is_err());
+        assert!(genpass(&["--attempts", "10"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_genpass_entries() -> Result<()> {
+        let opts = GenPassOpts::try_parse_from(["genpass", "--count", "3", "--charset", "0-9"])?;
+        let entries = process_genpass_entries(&opts)?;
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].entropy_bits, 53.15);
+        assert!(entries
+            .iter()
+            .all(|e| e.password.len() == 16 && e.score <= 4));
+
+        let mut out = Vec::new();
+        write_entries(&entries, GenPassFormat::Csv, &mut out)?;
+        let out = String::from_utf8(out)?;
+        assert!(out.starts_with("password,entropy_bits,score,warning,suggestions\n"));
+        assert_eq!(out.lines().count(), 4);
+
+        let mut out = Vec::new();
+        write_entries(&entries, GenPassFormat::Json, &mut out)?;
+        let json: serde_json::Value = serde_json::from_slice(&out)?;
+        assert_eq!(json[2]["password"], entries[2].password.as_str());
+        assert!(json[0]["suggestions"].is_array());
+
+        let opts = GenPassOpts::try_parse_from(["genpass", "--words", "6", "--digit"])?;
+        let bits = Generator::new(&opts)?.entropy_bits();
+        assert!((bits - (6.0 * 7776f64.log2() + 60f64.log2())).abs() < 1e-9);
+        let opts = GenPassOpts::try_parse_from(["genpass", "--count", "0"])?;
+        assert!(process_genpass_entries(&opts).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_passphrase() -> Result<()> {
+        assert_eq!(load_wordlist("eff-large")?.len(), 7776);
+        assert_eq!(load_wordlist("eff-short")?.len(), 1296);
+
+        let words = load_wordlist("eff-large")?;
+        let phrase = genpass(&["--words", "6"])?;
+        let parts: Vec<&str> = phrase.split('-').collect();
+        assert_eq!(parts.len(), 6);
+        assert!(
+            parts.iter().all(|p| words.iter().any(|w| w == p)),
+            "{phrase}"
+        );
+
+        let mut file = tempfile::NamedTempFile::new()?;
+        std::io::Write::write_all(&mut file, b"# team list\nalpha\nbravo\n\nalpha\n")?;
+        let path = file.path().to_string_lossy().to_string();
+        let args = ["--words", "4", "--wordlist", &path, "--separator", " "];
+        let phrase = genpass(&[&args[..], &["--capitalize", "--digit"]].concat())?;
+        let parts: Vec<&str> = phrase.split(' ').collect();
+        assert_eq!(parts.len(), 4);
+        assert_eq!(phrase.chars().filter(char::is_ascii_digit).count(), 1);
+        assert!(parts
+            .iter()
+            .all(|p| p.starts_with("Alpha") || p.starts_with("Bravo")));
+
+        assert!(genpass(&["--words", "0"]).is_err());
+        assert!(genpass(&["--words", "3", "--no-symbol"]).is_err());
+        assert!(genpass(&["--digit"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_genpass_impossible_options() {
+        let all_off = [
+            "--no-lowercase",
+            "--no-uppercase",
+            "--no-number",
+            "--no-symbol",
+        ];
+        assert!(genpass(&all_off).is_err());
+        assert!(genpass(&["-l", "3"]).is_err());
+        assert!(genpass(&["-l", "0", "--no-symbol", "--no-number", "--no-uppercase"]).is_err());
+        assert!(genpass(&["--symbol", "false"]).is_err());
+    }
 }
@@ -1,5 +1,27 @@
 pub mod base64;
+pub mod csv_cat;
+#[cfg(feature = "columnar")]
+pub mod csv_columnar;
 pub mod csv_convert;
+pub mod csv_dedupe;
+pub mod csv_diff;
+pub mod csv_filter;
+pub mod csv_from;
+pub mod csv_groupby;
+pub mod csv_join;
+pub mod csv_parallel;
+pub mod csv_query;
+pub mod csv_reader;
+pub mod csv_show;
+pub mod csv_sort;
+pub mod csv_split;
+pub mod csv_stats;
+pub mod csv_types;
+pub mod csv_validate;
+pub mod csv_writer;
 pub mod gen_pass;
 pub mod http_serve;
+pub mod pass_policy;
+pub mod spreadsheet;
+pub mod table;
 pub mod text;
@@ -0,0 +1,173 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use zxcvbn::zxcvbn;
+
+use crate::utils::load_document;
+
+/// 密码策略，从 toml / yaml / json 文件读取，未写的规则不做要求
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordPolicy {
+    pub min_uppercase: usize,
+    pub min_lowercase: usize,
+    pub min_digits: usize,
+    pub min_symbols: usize,
+    /// 同一字符最多连续出现的次数
+    pub max_consecutive: Option<usize>,
+    /// 不允许出现的子串，不区分大小写
+    pub forbidden: Vec<String>,
+    /// zxcvbn 最低评分，0 - 4
+    pub min_score: Option<u8>,
+}
+
+impl PasswordPolicy {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let policy: Self = load_document(path)
+            .with_context(|| format!("invalid password policy {}", path.display()))?;
+        if policy.max_consecutive == Some(0) {
+            bail!("max_consecutive must be greater than 0");
+        }
+        if policy.min_score.is_some_and(|score| score > 4) {
+            bail!("min_score must be between 0 and 4");
+        }
+        Ok(policy)
+    }
+
+    /// 各字符类别最少个数之和
+    pub fn min_length(&self) -> usize {
+        self.min_uppercase + self.min_lowercase + self.min_digits + self.min_symbols
+    }
+
+    /// 校验密码，返回第一条不满足的规则
+    pub fn check(&self, pwd: &str) -> Option<String> {
+        let counts = [
+            (
+                self.min_uppercase,
+                "uppercase letters",
+                char::is_uppercase as fn(char) -> bool,
+            ),
+            (self.min_lowercase, "lowercase letters", char::is_lowercase),
+            (self.min_digits, "digits", char::is_numeric),
+            (self.min_symbols, "symbols", |c: char| !c.is_alphanumeric()),
+        ];
+        for (min, name, matches) in counts {
+            if pwd.chars().filter(|c| matches(*c)).count() < min {
+                return Some(format!("at least {min} {name}"));
+            }
+        }
+        if let Some(max) = self.max_consecutive {
+            if longest_run(pwd) > max {
+                return Some(format!("at most {max} consecutive identical characters"));
+            }
+        }
+        let lower = pwd.to_lowercase();
+        if let Some(word) = self
+            .forbidden
+            .iter()
+            .find(|word| !word.is_empty() && lower.contains(&word.to_lowercase()))
+        {
+            return Some(format!("must not contain {word:?}"));
+        }
+        if let Some(min) = self.min_score {
+            if u8::from(zxcvbn(pwd, &[]).score()) < min {
+                return Some(format!("zxcvbn score at least {min}"));
+            }
+        }
+        None
+    }
+
+    /// 反复调用 `generate` 直到满足策略；`attempts` 次后仍不满足时报告失败最多的规则
+    pub fn generate(
+        &self,
+        attempts: usize,
+        mut generate: impl FnMut() -> Result<String>,
+    ) -> Result<String> {
+        let mut failures: Vec<(String, usize)> = Vec::new();
+        for _ in 0..attempts {
+            let pwd = generate()?;
+            let Some(rule) = self.check(&pwd) else {
+                return Ok(pwd);
+            };
+            match failures.iter_mut().find(|(r, _)| *r == rule) {
+                Some((_, n)) => *n += 1,
+                None => failures.push((rule, 1)),
+            }
+        }
+        match failures.into_iter().max_by_key(|(_, n)| *n) {
+            Some((rule, n)) => bail!(
+                "could not satisfy the password policy within {attempts} attempts: \
+                 rule \"{rule}\" failed {n} times"
+            ),
+            None => bail!("--attempts must be greater than 0"),
+        }
+    }
+}
+
+/// 同一字符连续出现的最大次数
+fn longest_run(pwd: &str) -> usize {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut prev = None;
+    for c in pwd.chars() {
+        run = if prev == Some(c) { run + 1 } else { 1 };
+        longest = longest.max(run);
+        prev = Some(c);
+    }
+    longest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_check() -> Result<()> {
+        let mut file = tempfile::Builder::new().suffix(".toml").tempfile()?;
+        std::io::Write::write_all(
+            &mut file,
+            b"min_digits = 2\nmin_symbols = 1\nmax_consecutive = 2\nforbidden = [\"juve\"]\n",
+        )?;
+        let policy = PasswordPolicy::load(file.path())?;
+        assert_eq!(policy.min_length(), 3);
+        assert_eq!(policy.check("ab1!"), Some("at least 2 digits".to_string()));
+        assert_eq!(
+            policy.check("ab12!!!"),
+            Some("at most 2 consecutive identical characters".to_string())
+        );
+        assert_eq!(
+            policy.check("JUVE12!"),
+            Some("must not contain \"juve\"".to_string())
+        );
+        assert_eq!(policy.check("Dybala-10-21"), None);
+
+        let policy = PasswordPolicy {
+            min_score: Some(3),
+            ..Default::default()
+        };
+        assert!(policy.check("password").is_some());
+        assert_eq!(
+            policy
+                .generate(5, || Ok("3rA!x9#Lq2@vZ".to_string()))?
+                .len(),
+            13
+        );
+        let err = policy
+            .generate(5, || Ok("password".to_string()))
+            .unwrap_err();
+        assert!(err.to_string().contains("zxcvbn score at least 3"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_policy_invalid() -> Result<()> {
+        for content in ["max_consecutive = 0", "min_score = 5", "min_digit = 2"] {
+            let mut file = tempfile::Builder::new().suffix(".toml").tempfile()?;
+            std::io::Write::write_all(&mut file, content.as_bytes())?;
+            assert!(PasswordPolicy::load(file.path()).is_err(), "{content}");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use calamine::{open_workbook_auto, Data, DataType, Reader};
+use csv::WriterBuilder;
+
+/// 支持的电子表格扩展名
+const EXTENSIONS: [&str; 5] = ["xlsx", "xlsm", "xlsb", "xls", "ods"];
+
+pub fn is_spreadsheet(input: &str) -> bool {
+    Path::new(input)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+pub fn sheet_names(input: &str) -> Result<Vec<String>> {
+    let workbook =
+        open_workbook_auto(input).with_context(|| format!("cannot open workbook {input:?}"))?;
+    Ok(workbook.sheet_names())
+}
+
+/// 将工作表转换为 csv 字节，`sheet` 为空时读取第一个工作表
+///
+/// 分隔符与引号沿用读取参数，以便后续按同样的方言解析。
+pub fn sheet_to_csv(input: &str, sheet: Option<&str>, delimiter: u8, quote: u8) -> Result<Vec<u8>> {
+    let mut workbook =
+        open_workbook_auto(input).with_context(|| format!("cannot open workbook {input:?}"))?;
+    let names = workbook.sheet_names();
+    let name = match sheet {
+        Some(sheet) => names
+            .iter()
+            .find(|name| *name == sheet)
+            .ok_or_else(|| anyhow!("sheet {sheet:?} not found, available: {names:?}"))?,
+        None => names
+            .first()
+            .ok_or_else(|| anyhow!("workbook {input:?} has no sheets"))?,
+    };
+    let range = workbook.worksheet_range(name)?;
+
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .quote(quote)
+        .flexible(true)
+        .from_writer(Vec::new());
+    for row in range.rows() {
+        writer.write_record(row.iter().map(cell_to_string))?;
+    }
+    Ok(writer.into_inner().map_err(|e| e.into_error())?)
+}
+
+/// 整数值的浮点数去掉小数部分，日期输出为 ISO 8601
+fn cell_to_string(cell: &Data) -> String {
+    match cell {
+        Data::Empty => String::new(),
+        Data::Float(f) if f.fract() == 0.0 && f.abs() < 1e15 => (*f as i64).to_string(),
+        Data::DateTime(_) => match cell.as_datetime() {
+            Some(dt) => {
+                let s = dt.to_string().replacen(' ', "T", 1);
+                s.strip_suffix("T00:00:00").map(String::from).unwrap_or(s)
+            }
+            None => cell.to_string(),
+        },
+        cell => cell.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORKBOOK: &str = "assets/juventus.xlsx";
+
+    #[test]
+    fn test_sheet_names() -> Result<()> {
+        assert!(is_spreadsheet(WORKBOOK));
+        assert!(!is_spreadsheet("assets/juventus.csv"));
+        assert_eq!(sheet_names(WORKBOOK)?, ["Staff", "Roster"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sheet_to_csv() -> Result<()> {
+        let csv = sheet_to_csv(WORKBOOK, Some("Roster"), b',', b'"')?;
+        assert_eq!(
+            String::from_utf8(csv)?,
+            "Name,Kit Number,Joined,Captain,Rating\n\
+             Gianluigi Buffon,77,2001-07-03,true,88.5\n\
+             \"Giorgio Chiellini, Jr\",3,2017-07-01,false,\n"
+        );
+
+        let csv = sheet_to_csv(WORKBOOK, None, b';', b'"')?;
+        assert_eq!(
+            String::from_utf8(csv)?,
+            "Name;Role\nMassimiliano Allegri;Manager\n"
+        );
+        assert!(sheet_to_csv(WORKBOOK, Some("Academy"), b',', b'"').is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use std::io::{IsTerminal, Write};
+
+use anyhow::Result;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use super::csv_types::infer_type;
+
+/// 带边框的终端表格，按显示宽度对齐（兼容中文等宽字符）
+#[derive(Debug)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    max_width: usize,
+    color: bool,
+}
+
+impl Table {
+    pub fn new(headers: Vec<String>) -> Self {
+        Self {
+            headers,
+            rows: Vec::new(),
+            max_width: usize::MAX,
+            color: false,
+        }
+    }
+
+    /// 单元格最大显示宽度，超出部分截断为 `…`
+    pub fn max_width(mut self, max_width: usize) -> Self {
+        self.max_width = max_width.max(1);
+        self
+    }
+
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn push_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    pub fn render(&self, writer: &mut impl Write) -> Result<()> {
+        let headers: Vec<String> = self.headers.iter().map(|h| self.truncate(h)).collect();
+        let rows: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|cell| self.truncate(cell)).collect())
+            .collect();
+
+        let mut widths: Vec<usize> = headers.iter().map(|h| h.width()).collect();
+        for row in &rows {
+            for (i, cell) in row.iter().enumerate() {
+                match widths.get_mut(i) {
+                    Some(width) => *width = (*width).max(cell.width()),
+                    None => widths.push(cell.width()),
+                }
+            }
+        }
+
+        self.write_border(writer, &widths, ('┌', '┬', '┐'))?;
+        self.write_row(writer, &widths, &headers, true)?;
+        self.write_border(writer, &widths, ('├', '┼', '┤'))?;
+        for row in &rows {
+            self.write_row(writer, &widths, row, false)?;
+        }
+        self.write_border(writer, &widths, ('└', '┴', '┘'))?;
+        Ok(())
+    }
+
+    fn truncate(&self, cell: &str) -> String {
+        // 换行与制表符会破坏表格布局
+        let cell = cell.replace(['\n', '\r', '\t'], " ");
+        if cell.width() <= self.max_width {
+            return cell;
+        }
+        let mut out = String::new();
+        let mut width = 0;
+        for c in cell.chars() {
+            let w = c.width().unwrap_or(0);
+            if width + w > self.max_width - 1 {
+                break;
+            }
+            out.push(c);
+            width += w;
+        }
+        out.push('…');
+        out
+    }
+
+    fn write_border(
+        &self,
+        writer: &mut impl Write,
+        widths: &[usize],
+        (left, mid, right): (char, char, char),
+    ) -> Result<()> {
+        let line = widths
+            .iter()
+            .map(|w| "─".repeat(w + 2))
+            .collect::<Vec<_>>()
+            .join(&mid.to_string());
+        writeln!(writer, "{left}{line}{right}")?;
+        Ok(())
+    }
+
+    fn write_row(
+        &self,
+        writer: &mut impl Write,
+        widths: &[usize],
+        row: &[String],
+        header: bool,
+    ) -> Result<()> {
+        write!(writer, "│")?;
+        for (i, width) in widths.iter().enumerate() {
+            let cell = row.get(i).map(String::as_str).unwrap_or_default();
+            let pad = " ".repeat(width - cell.width());
+            let numeric = !header && infer_type(cell).is_some_and(|ty| ty.is_numeric());
+            let text = if header && self.color {
+                format!("\x1b[1;36m{cell}\x1b[0m")
+            } else {
+                cell.to_string()
+            };
+            if numeric {
+                write!(writer, " {pad}{text} │")?;
+            } else {
+                write!(writer, " {text}{pad} │")?;
+            }
+        }
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+/// 仅在 stdout 为终端且未设置 `NO_COLOR` 时输出颜色
+pub fn use_color() -> bool {
+    std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()) && std::io::stdout().is_terminal()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_table() -> Result<()> {
+        let mut table = Table::new(vec!["name".into(), "kit".into()]).max_width(6);
+        table.push_row(vec!["Buffon".into(), "77".into()]);
+        table.push_row(vec!["布冯球员".into(), "1".into()]);
+        table.push_row(vec!["Chiellini".into(), "3".into()]);
+
+        let mut buf = Vec::new();
+        table.render(&mut buf)?;
+        assert_eq!(
+            String::from_utf8(buf)?,
+            "┌────────┬─────┐\n\
+             │ name   │ kit │\n\
+             ├────────┼─────┤\n\
+             │ Buffon │  77 │\n\
+             │ 布冯…  │   1 │\n\
+             │ Chiel… │   3 │\n\
+             └────────┴─────┘\n"
+        );
+        Ok(())
+    }
+}
@@ -106,10 +106,7 @@ impl KeyGenerator for Blake3 {
     fn generate() -> Result<Vec<Vec<u8>>> {
         let key = process_genpass(GenPassOpts {
             length: 32,
-            uppercase: true,
-            lowercase: true,
-            number: true,
-            symbol: true,
+            ..Default::default()
         })?;
         let key = vec![key.into_bytes()];
         Ok(key)
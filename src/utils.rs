@@ -1,6 +1,11 @@
-use std::{fs::File, io::Read};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Read, Write},
+    path::Path,
+};
 
 use anyhow::Result;
+use serde::de::DeserializeOwned;
 
 pub fn read_input(input: &str, buf: &mut Vec<u8>) -> Result<()> {
     let mut reader = get_reader(input)?;
@@ -17,3 +22,56 @@ pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
         Box::new(File::open(input)?)
     })
 }
+
+pub fn get_writer(output: &str) -> Result<Box<dyn Write>> {
+    Ok(if output == "-" {
+        Box::new(BufWriter::new(std::io::stdout()))
+    } else {
+        Box::new(BufWriter::new(File::create(output)?))
+    })
+}
+
+/// 未指定输出时：输入为 stdin 则写到 stdout，否则写到 `<input>.<ext>`
+pub fn default_output(input: &str, output: Option<&str>, ext: &str) -> String {
+    match output {
+        Some(output) => output.to_string(),
+        None if input == "-" => "-".to_string(),
+        None => format!("{input}.{ext}"),
+    }
+}
+
+/// 根据扩展名解析 json / yaml / toml 文件，默认按 json 解析
+pub fn load_document<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path)?;
+    Ok(match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str(&content)?,
+        Some("yaml") | Some("yml") => serde_yaml::from_str(&content)?,
+        _ => serde_json::from_str(&content)?,
+    })
+}
+
+/// 可共享的内存输出缓冲区，满足 `Box<dyn Write>` 的 `'static` 约束
+#[derive(Clone, Default)]
+pub struct SharedBuf(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl SharedBuf {
+    pub fn contents(&self) -> Result<String> {
+        Ok(String::from_utf8(self.0.lock().unwrap().clone())?)
+    }
+
+    /// 取出已写入的内容并清空缓冲区
+    pub fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
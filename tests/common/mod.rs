@@ -0,0 +1,80 @@
+//! 流式转换的测试与 bench 共用：统计堆内存的分配器与按需生成的 csv 输入
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    io::{self, Read},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// 记录当前与峰值堆内存的分配器
+pub struct CountingAlloc;
+
+pub static CURRENT: AtomicUsize = AtomicUsize::new(0);
+pub static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let now = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(now, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// 按需生成 csv 内容并统计生成的字节数，避免输入本身占用大量内存
+pub struct GeneratedCsv {
+    rows: usize,
+    next: usize,
+    pending: Vec<u8>,
+    pos: usize,
+    pub bytes: usize,
+}
+
+impl GeneratedCsv {
+    pub fn new(rows: usize) -> Self {
+        Self {
+            rows,
+            next: 0,
+            pending: b"id,name,club,score\n".to_vec(),
+            pos: 0,
+            bytes: 0,
+        }
+    }
+}
+
+impl Read for GeneratedCsv {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.pending.len() {
+            if self.next == self.rows {
+                return Ok(0);
+            }
+            self.pending.clear();
+            self.pending.extend_from_slice(
+                format!(
+                    "{},player {},Juventus,{}\n",
+                    self.next,
+                    self.next,
+                    self.next % 100
+                )
+                .as_bytes(),
+            );
+            self.pos = 0;
+            self.next += 1;
+        }
+        let n = buf.len().min(self.pending.len() - self.pos);
+        buf[..n].copy_from_slice(&self.pending[self.pos..self.pos + n]);
+        self.pos += n;
+        self.bytes += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use std::{io, sync::atomic::Ordering};
+
+use anyhow::Result;
+use clap::Parser;
+use common::{GeneratedCsv, CURRENT, PEAK};
+use rcli::{
+    cli::csv::{CsvOpts, OutputFormat},
+    process::csv_convert::convert_csv,
+};
+
+fn opts(format: OutputFormat) -> CsvOpts {
+    let format = format.to_string();
+    CsvOpts::parse_from(["csv", "-i", "-", "-f", &format, "--infer-types"])
+}
+
+#[test]
+fn convert_csv_uses_constant_memory() -> Result<()> {
+    const ROWS: usize = 100_000;
+
+    for format in [OutputFormat::Json, OutputFormat::Ndjson, OutputFormat::Yaml] {
+        let base = CURRENT.load(Ordering::SeqCst);
+        PEAK.store(base, Ordering::SeqCst);
+
+        let mut input = GeneratedCsv::new(ROWS);
+        let rows = convert_csv(&opts(format.clone()), &mut input, Box::new(io::sink()))?;
+        assert_eq!(rows, ROWS);
+
+        // ~4MB 的输入，峰值额外内存应远小于输入大小
+        let peak = PEAK.load(Ordering::SeqCst) - base;
+        assert!(
+            peak < 256 * 1024 && peak < input.bytes / 16,
+            "{format}: peak heap usage {peak} bytes for {} bytes of input",
+            input.bytes
+        );
+    }
+
+    Ok(())
+}